@@ -0,0 +1,370 @@
+//! Settings of a bot run.
+//!
+//! Settings are read from a TOML file and overridden by the command line, settings missing from
+//! both keep their defaults:
+//!
+//! ```toml
+//! [game]
+//! addr = "127.0.0.1:8000"
+//! map = "/opt/StarCraftII/Maps/EphemeronLE.SC2Map"
+//! mode = "step"
+//! step_size = 2
+//!
+//! [bot]
+//! name = "yolo, in the game"
+//! race = "Terran"
+//!
+//! [opponent]
+//! name = "sentient cheese dip"
+//! race = "Zerg"
+//! difficulty = "Hard"
+//! build = "Rush"
+//!
+//! [store]
+//! endpoint = "ws://localhost:8001"
+//! namespace = "sc2bot"
+//! database = "test"
+//! username = "root"
+//! password = "root"
+//! ```
+//!
+//! Races, difficulties and builds use the names of the protocol enums.
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use rsc2::{
+    prelude::{Difficulty, Player, Race, StepMode},
+    protocol::{AiBuild, PlayerSetup},
+};
+use serde::{Deserialize, Deserializer, de::Error as _};
+use surrealdb::opt::auth::Root;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub game: GameConfig,
+    pub bot: BotConfig,
+    pub opponent: OpponentConfig,
+    pub store: StoreConfig,
+}
+
+/// How the simulation advances, see [`StepMode`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Realtime,
+    Step,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    /// Address of the game api
+    pub addr: String,
+    /// Local map path or Battle.net map name
+    pub map: String,
+    pub mode: Mode,
+    /// Game loops between two observations
+    pub step_size: u32,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            addr: "127.0.0.1:8000".into(),
+            map: r"C:\Program Files (x86)\StarCraft II\Maps\EphemeronLE.SC2Map".into(),
+            mode: Mode::Realtime,
+            step_size: 1,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn step_mode(&self) -> StepMode {
+        match self.mode {
+            Mode::Realtime => StepMode::Realtime(self.step_size),
+            Mode::Step => StepMode::Step(self.step_size),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    pub name: String,
+    #[serde(deserialize_with = "race")]
+    pub race: Race,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        Self {
+            name: "yolo, in the game".into(),
+            race: Race::Terran,
+        }
+    }
+}
+
+/// Computer player the bot plays against
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpponentConfig {
+    pub name: String,
+    #[serde(deserialize_with = "race")]
+    pub race: Race,
+    #[serde(deserialize_with = "difficulty")]
+    pub difficulty: Difficulty,
+    /// Picked at random by the game when missing
+    #[serde(deserialize_with = "build")]
+    pub build: Option<AiBuild>,
+}
+
+impl Default for OpponentConfig {
+    fn default() -> Self {
+        Self {
+            name: "sentient cheese dip".into(),
+            race: Race::Zerg,
+            difficulty: Difficulty::Easy,
+            build: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreConfig {
+    /// `memory` or a SurrealDB endpoint, see [`Backend`](crate::store::Backend). Defaults to
+    /// [`StoreConfig::DEFAULT_ENDPOINT`], or to `memory` in ladder games.
+    pub endpoint: Option<String>,
+    pub namespace: String,
+    pub database: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            namespace: "sc2bot".into(),
+            database: "test".into(),
+            username: None,
+            password: None,
+        }
+    }
+}
+
+impl StoreConfig {
+    pub const DEFAULT_ENDPOINT: &str = "localhost:8001";
+
+    pub fn endpoint(&self) -> &str {
+        self.endpoint.as_deref().unwrap_or(Self::DEFAULT_ENDPOINT)
+    }
+
+    /// Root user to sign in as, `None` unless both the username and password are set
+    pub fn credentials(&self) -> Option<Root<'_>> {
+        Some(Root {
+            username: self.username.as_deref()?,
+            password: self.password.as_deref()?,
+        })
+    }
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let config = std::fs::read_to_string(path)
+            .with_context(|| format!("Reading config {}", path.display()))?;
+        toml::from_str(&config).with_context(|| format!("Parsing config {}", path.display()))
+    }
+
+    /// Player of the bot
+    pub fn player(&self) -> PlayerSetup {
+        Player::participant(self.bot.name.clone(), self.bot.race).into()
+    }
+
+    /// Players of the game, the bot first
+    pub fn players(&self) -> [PlayerSetup; 2] {
+        let opponent = &self.opponent;
+        let mut computer = Player::bot(opponent.name.clone(), opponent.race, opponent.difficulty);
+        if let Some(build) = opponent.build {
+            computer = computer.with_build(build);
+        }
+        [self.player(), computer.into()]
+    }
+}
+
+/// Plays a game against the computer and stores its observations
+#[derive(Debug, Default, Parser)]
+#[command(about)]
+pub struct Cli {
+    /// TOML config, see the `config` module for its layout
+    #[arg(short, long, env = "BOT_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Address of the game api
+    #[arg(long)]
+    pub addr: Option<String>,
+    /// Local map path or Battle.net map name
+    #[arg(long)]
+    pub map: Option<String>,
+    #[arg(long)]
+    pub mode: Option<Mode>,
+    /// Game loops between two observations
+    #[arg(long)]
+    pub step_size: Option<u32>,
+    #[arg(long, value_parser = parse_race)]
+    pub race: Option<Race>,
+    #[arg(long, value_parser = parse_race)]
+    pub opponent_race: Option<Race>,
+    #[arg(long, value_parser = parse_difficulty)]
+    pub difficulty: Option<Difficulty>,
+    #[arg(long, value_parser = parse_build)]
+    pub build: Option<AiBuild>,
+    /// `memory` or a SurrealDB endpoint (`ws://host:port`, `mem://`, `rocksdb://<path>`)
+    #[arg(long, env = "BOT_STORE")]
+    pub store: Option<String>,
+    #[arg(long)]
+    pub namespace: Option<String>,
+    #[arg(long)]
+    pub database: Option<String>,
+    #[arg(long)]
+    pub username: Option<String>,
+    #[arg(long, env = "BOT_DB_PASSWORD", hide_env_values = true)]
+    pub password: Option<String>,
+    /// Print the migrations missing from the store and exit without applying them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+impl Cli {
+    /// Reads the config file if any and applies the arguments over it
+    pub fn config(self) -> anyhow::Result<Config> {
+        let config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        Ok(self.apply(config))
+    }
+
+    /// Overrides the settings of `config` set on the command line
+    pub fn apply(self, mut config: Config) -> Config {
+        let Config {
+            game,
+            bot,
+            opponent,
+            store,
+        } = &mut config;
+        override_with(&mut game.addr, self.addr);
+        override_with(&mut game.map, self.map);
+        override_with(&mut game.mode, self.mode);
+        override_with(&mut game.step_size, self.step_size);
+        override_with(&mut bot.race, self.race);
+        override_with(&mut opponent.race, self.opponent_race);
+        override_with(&mut opponent.difficulty, self.difficulty);
+        if self.build.is_some() {
+            opponent.build = self.build;
+        }
+        if self.store.is_some() {
+            store.endpoint = self.store;
+        }
+        override_with(&mut store.namespace, self.namespace);
+        override_with(&mut store.database, self.database);
+        if self.username.is_some() {
+            store.username = self.username;
+        }
+        if self.password.is_some() {
+            store.password = self.password;
+        }
+        config
+    }
+}
+
+fn override_with<T>(setting: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *setting = value;
+    }
+}
+
+fn parse_race(name: &str) -> Result<Race, String> {
+    Race::from_str_name(name).ok_or_else(|| format!("unknown race {name}"))
+}
+
+fn parse_difficulty(name: &str) -> Result<Difficulty, String> {
+    Difficulty::from_str_name(name).ok_or_else(|| format!("unknown difficulty {name}"))
+}
+
+fn parse_build(name: &str) -> Result<AiBuild, String> {
+    AiBuild::from_str_name(name).ok_or_else(|| format!("unknown build {name}"))
+}
+
+fn race<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Race, D::Error> {
+    parse_race(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+fn difficulty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Difficulty, D::Error> {
+    parse_difficulty(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+fn build<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<AiBuild>, D::Error> {
+    parse_build(&String::deserialize(deserializer)?)
+        .map(Some)
+        .map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides() {
+        let config: Config = toml::from_str(
+            r#"
+            [game]
+            map = "EphemeronLE.SC2Map"
+            mode = "step"
+            step_size = 4
+
+            [opponent]
+            race = "Protoss"
+            build = "Air"
+
+            [store]
+            endpoint = "memory"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.game.addr, "127.0.0.1:8000");
+        assert_eq!(config.game.step_mode(), StepMode::Step(4));
+        assert_eq!(config.opponent.difficulty, Difficulty::Easy);
+        assert_eq!(config.opponent.build, Some(AiBuild::Air));
+        assert_eq!(config.store.endpoint(), "memory");
+        assert!(config.store.credentials().is_none());
+
+        let config = Cli::parse_from([
+            "bot",
+            "--mode",
+            "realtime",
+            "--difficulty",
+            "VeryHard",
+            "--username",
+            "root",
+            "--password",
+            "root",
+        ])
+        .apply(config);
+        assert_eq!(config.game.step_mode(), StepMode::Realtime(4));
+        assert_eq!(config.game.map, "EphemeronLE.SC2Map");
+        assert_eq!(config.opponent.difficulty, Difficulty::VeryHard);
+        assert_eq!(config.opponent.build, Some(AiBuild::Air));
+        assert!(config.store.credentials().is_some());
+        assert_eq!(
+            Config::default().store.endpoint(),
+            StoreConfig::DEFAULT_ENDPOINT
+        );
+
+        assert!(toml::from_str::<Config>("[opponent]\nrace = \"Elf\"").is_err());
+        assert!(Cli::try_parse_from(["bot", "--race", "Elf"]).is_err());
+    }
+}
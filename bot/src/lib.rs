@@ -0,0 +1,5 @@
+//! Game state storage of the bot, observations are recorded into SurrealDB for analysis.
+pub mod config;
+pub mod queries;
+pub mod store;
+pub mod throughput;
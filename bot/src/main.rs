@@ -1,147 +1,200 @@
-mod queries;
-mod store;
-mod throughput;
+use std::time::Instant;
 
-use std::{io, sync::Arc, time::Duration};
-
-use anyhow::Context;
-use futures::{SinkExt, StreamExt};
-use log::info;
+use bot::{
+    config::{Cli, StoreConfig},
+    store::{self, Backend, GameStore, MemoryStore, SurrealStore, World, Writer, WriterOptions},
+    throughput,
+};
+use clap::Parser;
 use rsc2::{
-    prelude::{Difficulty, Player, Race, create_game},
+    ladder::LadderArgs,
+    prelude::{Actions, Agent, run_game},
     protocol,
     state_machine::Core,
 };
-use surrealdb::{
-    Surreal,
-    engine::remote::ws::{Client, Ws},
-};
-
-use crate::store::World;
 
 struct Bot {
-    world: World,
+    writer: Writer,
+    throughput: throughput::RollingRecorder<16>,
+    last_step: Option<Instant>,
+    steps: usize,
 }
 
 impl Bot {
-    async fn new(db: Arc<Surreal<Client>>) -> Self {
+    fn new(writer: Writer) -> Self {
         Self {
-            world: World::new(db),
+            writer,
+            throughput: throughput::RollingRecorder::new(),
+            last_step: None,
+            steps: 0,
         }
     }
 
-    async fn update(&self, response: protocol::Response) -> anyhow::Result<()> {
-        let protocol::Response {
-            response: Some(protocol::response::Response::Observation(obs)),
-            ..
-        } = response
-        else {
-            log::trace!("Received non-observation response");
-            return Ok(());
-        };
-
-        if let Some(observation) = obs.observation.map(|obs| obs.raw_data).flatten() {
-            self.world.register_observation_raw(observation).await?;
+    async fn update(&mut self, obs: &protocol::ResponseObservation) -> anyhow::Result<()> {
+        if let Some(observation) = obs.observation.as_ref()
+            && let Some(raw) = observation.raw_data.clone()
+        {
+            self.writer
+                .observation(observation.game_loop(), raw)
+                .await?;
         }
         Ok(())
     }
 }
 
-async fn request_observation(gameloop: &mut rsc2::InGameListener<'_, '_>) -> Result<(), io::Error> {
-    info!("Requesting observation");
-    let mut req = protocol::Request::default();
-    req.request = Some(protocol::request::Request::Observation(
-        protocol::RequestObservation {
-            disable_fog: Some(false),
-            game_loop: None,
-        },
-    ));
-    gameloop.send(req).await
-}
+impl Agent for Bot {
+    async fn on_start(
+        &mut self,
+        game_info: &protocol::ResponseGameInfo,
+        _: &protocol::ResponseData,
+    ) {
+        if let Err(e) = self.writer.start_game(game_info.clone()).await {
+            log::error!("Error registering game: {}", e);
+        }
+    }
 
-async fn init_bot() -> anyhow::Result<Bot> {
-    // Initialize the SurrealDB connection
-    let store = Arc::new(Surreal::new::<Ws>("localhost:8001").await?);
-    store.use_ns("sc2bot").use_db("test").await?;
-    log::info!("Game state connection initialized");
-
-    // Ensure the database is created and tables are defined
-    store
-        .query(
-            queries::get("create_database")
-                .await
-                .with_context(|| "Expected create_database query to exist")?,
-        )
-        .await?
-        .check()?;
-
-    log::info!("SurrealDB tables defined");
-
-    Ok(Bot::new(store).await)
-}
+    async fn on_step(&mut self, obs: &protocol::ResponseObservation) -> Actions {
+        if let Err(e) = self.update(obs).await {
+            log::error!("Error updating bot: {}", e);
+        }
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> anyhow::Result<()> {
-    pretty_env_logger::init_timed();
+        // record throughput
+        let now = Instant::now();
+        if let Some(last_step) = self.last_step.replace(now) {
+            self.throughput
+                .record(now.duration_since(last_step).as_millis() as f64);
+            let tp_ms = self.throughput.get_average();
+            log::trace!(
+                "Game loop iteration {}; throughput: {:.4}ms/it | {:.4}it/s",
+                self.steps,
+                tp_ms,
+                1_000.0 / tp_ms
+            );
+        }
+        self.steps += 1;
 
-    let bot = Arc::new(init_bot().await?);
+        Actions::new()
+    }
 
-    let mut sm = Core::init();
+    async fn on_end(&mut self, results: &[protocol::PlayerResult]) {
+        if let Err(e) = self.writer.end_game(results.to_vec()).await {
+            log::error!("Error registering results: {}", e);
+        }
+        if self.writer.dropped() > 0 {
+            log::warn!("{} observations were not stored", self.writer.dropped());
+        }
+        log::info!(
+            "Game loop finished gracefully after {} iterations: {:?}",
+            self.steps,
+            results
+        );
+    }
+}
+
+fn spawn<S: GameStore>(store: S) -> Writer {
+    Writer::spawn(World::new(store), WriterOptions::default())
+}
 
-    let (state, mut connection) = create_game(
-        &mut sm,
-        "127.0.0.1:8000",
-        [
-            Player::participant("yolo, in the game", Race::Terran),
-            Player::bot("sentient cheese dip", Race::Zerg, Difficulty::Easy),
-        ],
-        r"C:\Program Files (x86)\StarCraft II\Maps\EphemeronLE.SC2Map",
-        true,
+/// Opens the store of `config`, see [`Backend`]
+async fn open_store(config: &StoreConfig) -> anyhow::Result<Writer> {
+    let Ok(backend) = config.endpoint().parse::<Backend>();
+    Ok(match backend {
+        Backend::Memory => spawn(MemoryStore::default()),
+        Backend::Surreal(endpoint) => spawn(
+            SurrealStore::open(
+                &endpoint,
+                &config.namespace,
+                &config.database,
+                config.credentials(),
+            )
+            .await?,
+        ),
+    })
+}
+
+/// Prints the migrations missing from the store of `config` without applying them
+async fn print_migrations(config: &StoreConfig) -> anyhow::Result<()> {
+    let Ok(backend) = config.endpoint().parse::<Backend>();
+    let Backend::Surreal(endpoint) = backend else {
+        println!("{} has no schema to migrate", config.endpoint());
+        return Ok(());
+    };
+    let db = store::connect(
+        &endpoint,
+        &config.namespace,
+        &config.database,
+        config.credentials(),
     )
     .await?;
+    let pending = store::migrate(&db, true).await?;
+    if pending.is_empty() {
+        println!("{endpoint} is up to date");
+    }
+    for migration in pending {
+        println!(
+            "pending migration {} ({})",
+            migration.version, migration.name
+        );
+    }
+    Ok(())
+}
 
-    let mut gameloop = state.stream(&mut connection);
-    let mut idx = 0;
-
-    let mut throughput_recorder = throughput::RollingRecorder::<16>::new();
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    pretty_env_logger::init_timed();
 
-    // wait for game to start
-    tokio::time::sleep(Duration::from_secs(3)).await;
+    // ladder managers start the bot with their own arguments, settings then come from the
+    // environment and the config file of `BOT_CONFIG`
+    let ladder = LadderArgs::from_env()?;
+    let cli = match ladder {
+        Some(_) => Cli::parse_from(std::env::args().take(1)),
+        None => Cli::parse(),
+    };
+    let dry_run = cli.dry_run;
+    let mut config = cli.config()?;
+    if ladder.is_some() {
+        // ladder hosts don't run a database next to the bot
+        config.store.endpoint.get_or_insert_with(|| "memory".into());
+    }
+    if dry_run {
+        return print_migrations(&config.store).await;
+    }
+    let mut bot = Bot::new(open_store(&config.store).await?);
 
-    // Start the game loop
-    request_observation(&mut gameloop).await?;
-    while let Some(response) = gameloop.next().await {
-        let loop_start = std::time::Instant::now();
-        let Ok(response) = response else {
-            let e = response.unwrap_err();
-            log::error!("Error in game loop: {}", e);
-            break;
-        };
+    let mut sm = Core::init();
 
-        if let Err(e) = bot.update(response).await {
-            log::error!("Error updating bot: {}", e);
+    match ladder {
+        Some(ladder) => {
+            log::info!(
+                "Joining ladder game on {}:{} against {}",
+                ladder.ladder_server,
+                ladder.game_port,
+                ladder
+                    .opponent_id
+                    .as_deref()
+                    .unwrap_or("an unknown opponent")
+            );
+            rsc2::ladder::run_game(
+                &mut sm,
+                &ladder,
+                config.player(),
+                config.game.step_size,
+                &mut bot,
+            )
+            .await?;
+        }
+        None => {
+            run_game(
+                &mut sm,
+                config.game.addr.as_str(),
+                config.players(),
+                config.game.map.as_str(),
+                config.game.step_mode(),
+                &mut bot,
+            )
+            .await?;
         }
-
-        // request next observation
-        request_observation(&mut gameloop).await?;
-
-        // record throughput
-        throughput_recorder.record(
-            std::time::Instant::now()
-                .duration_since(loop_start)
-                .as_millis() as f64,
-        );
-        let tp_ms = throughput_recorder.get_average();
-        log::trace!(
-            "Game loop iteration {idx}; throughput: {:.4}ms/it | {:.4}it/s",
-            tp_ms,
-            1_000.0 / tp_ms
-        );
-        idx += 1;
     }
-    let _ended = gameloop.into_ended();
 
-    log::info!("Game loop finished gracefully after {} iterations", idx);
-
-    Ok(())
+    bot.writer.close().await
 }
@@ -1,32 +1,105 @@
-use moka::future::Cache;
-use std::{
-    cell::LazyCell,
-    path::{Path, PathBuf},
-    time::Duration,
-};
-
-const CURRENT_DIR: LazyCell<PathBuf> =
-    LazyCell::new(|| Path::new(file!()).parent().unwrap().to_path_buf());
-
-const QUERY_CACHE: LazyCell<Cache<String, String>> = LazyCell::new(|| {
-    Cache::builder()
-        .max_capacity(64)
-        .time_to_idle(Duration::from_secs(15))
-        .build()
-});
-
-pub async fn get(name: impl ToString) -> Option<String> {
-    let name = name.to_string();
-    QUERY_CACHE
-        .optionally_get_with(name.clone(), async move {
-            let path = CURRENT_DIR.join(format!("{}.surql", name));
-
-            if !path.exists() {
-                return None;
-            }
-
-            let content = tokio::fs::read_to_string(&path).await.ok()?;
-            Some(content)
-        })
-        .await
+//! SurrealQL queries of the store, embedded in the binary at build time.
+use std::fmt;
+
+use anyhow::Context;
+
+/// Queries run by [`SurrealStore`](crate::store::SurrealStore)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Query {
+    RegisterGame,
+    RegisterResult,
+    RegisterObservation,
+    Trajectory,
+}
+
+impl Query {
+    pub const ALL: [Self; 4] = [
+        Self::RegisterGame,
+        Self::RegisterResult,
+        Self::RegisterObservation,
+        Self::Trajectory,
+    ];
+
+    /// Name of the `.surql` file of the query
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::RegisterGame => "register_game",
+            Self::RegisterResult => "register_result",
+            Self::RegisterObservation => "register_observation",
+            Self::Trajectory => "trajectory",
+        }
+    }
+
+    pub const fn source(self) -> &'static str {
+        match self {
+            Self::RegisterGame => include_str!("register_game.surql"),
+            Self::RegisterResult => include_str!("register_result.surql"),
+            Self::RegisterObservation => include_str!("register_observation.surql"),
+            Self::Trajectory => include_str!("trajectory.surql"),
+        }
+    }
+
+    /// Parameters bound before running the query, checked by the store as they are bound
+    pub const fn params(self) -> &'static [&'static str] {
+        match self {
+            Self::RegisterGame => &["game", "players"],
+            Self::RegisterResult => &["game", "ended_at", "results"],
+            Self::RegisterObservation => &[
+                "upsert_unit",
+                "upsert_position",
+                "upsert_has_position",
+                "upsert_unit_state",
+                "dead_units",
+                "fogged_units",
+            ],
+            Self::Trajectory => &["unit", "from", "to"],
+        }
+    }
+
+    /// Parses every query with the SurrealQL parser of the database, run at startup so a broken
+    /// query fails before a game is played rather than on its first use
+    pub fn check_all() -> anyhow::Result<()> {
+        for query in Self::ALL {
+            surrealdb::syn::parse(query.source())
+                .with_context(|| format!("Parsing the {query} query"))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    /// Parameters set by SurrealDB or by the query itself
+    const LOCAL_PARAMS: &[&str] = &["input", "death"];
+
+    #[test]
+    fn test_queries() {
+        Query::check_all().unwrap();
+        for query in Query::ALL {
+            let source = query.source();
+            let used: BTreeSet<&str> = source
+                .match_indices('$')
+                .map(|(i, _)| {
+                    let name = &source[i + 1..];
+                    let end = name
+                        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                        .unwrap_or(name.len());
+                    &name[..end]
+                })
+                .filter(|name| !LOCAL_PARAMS.contains(name))
+                .collect();
+            let params = query.params().iter().copied().collect();
+            assert_eq!(used, params, "parameters of the {query} query");
+        }
+    }
 }
@@ -0,0 +1,292 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use rsc2::{
+    geometry::Point3,
+    protocol::{self, unit_order::Target},
+};
+use surrealdb::{Datetime, RecordId};
+
+use super::{
+    Death, HasPosition, Order, Position, UNIT_STATE_VERSION, Unit, UnitState, UnitStatus,
+    presence::Presence, scoped_key, unit_record,
+};
+
+/// Records of consecutive observations of a game, written in one transaction
+#[derive(Debug, Default)]
+pub struct ObservationRecords {
+    /// One record per unit, upserted before the deaths and fogged units are applied
+    pub units: Vec<Unit>,
+    pub positions: Vec<Position>,
+    pub has_position: Vec<HasPosition>,
+    pub unit_states: Vec<UnitState>,
+    pub deaths: Vec<Death>,
+    /// Units that left the observation without dying
+    pub fogged: Vec<RecordId>,
+}
+
+impl ObservationRecords {
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty() && self.deaths.is_empty() && self.fogged.is_empty()
+    }
+}
+
+/// Builds the [`ObservationRecords`] of consecutive observations
+pub(super) struct Batch {
+    game: RecordId,
+    records: ObservationRecords,
+    /// Index of each unit in `records.units`, a unit seen by several observations is upserted
+    /// once
+    indices: HashMap<RecordId, usize>,
+}
+
+impl Batch {
+    pub fn new(game: RecordId) -> Self {
+        Self {
+            game,
+            records: ObservationRecords::default(),
+            indices: HashMap::new(),
+        }
+    }
+
+    pub fn into_records(self) -> ObservationRecords {
+        self.records
+    }
+
+    /// Upserts `unit`, keeping the last game loop it was visible at
+    fn upsert(&mut self, mut unit: Unit) {
+        self.records.fogged.retain(|id| *id != unit.id);
+        match self.indices.get(&unit.id) {
+            Some(&i) => {
+                unit.last_seen = unit.last_seen.or(self.records.units[i].last_seen);
+                self.records.units[i] = unit;
+            }
+            None => {
+                self.indices
+                    .insert(unit.id.clone(), self.records.units.len());
+                self.records.units.push(unit);
+            }
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        game_loop: u32,
+        observation: protocol::ObservationRaw,
+        presence: &mut Presence,
+    ) {
+        let game = self.game.clone();
+        let now = Datetime::from(Utc::now());
+        let protocol::ObservationRaw { units, event, .. } = observation;
+
+        let mut present = HashSet::with_capacity(units.len());
+        for unit in units {
+            let visible = unit.display_type() == protocol::DisplayType::Visible;
+            let pos = unit.pos.map(Point3::from).unwrap_or_default();
+            present.insert(unit.tag());
+            let unit_id = unit_record(&game, unit.tag());
+            self.upsert(Unit {
+                id: unit_id.clone(),
+                game: game.clone(),
+                unit_type: unit.unit_type(),
+                status: if visible {
+                    UnitStatus::Visible
+                } else {
+                    UnitStatus::Fogged
+                },
+                last_seen: visible.then_some(game_loop),
+                died_at: None,
+                transport: None,
+            });
+            for passenger in &unit.passengers {
+                present.insert(passenger.tag());
+                self.upsert(Unit {
+                    id: unit_record(&game, passenger.tag()),
+                    game: game.clone(),
+                    unit_type: passenger.unit_type(),
+                    status: UnitStatus::Transported,
+                    last_seen: Some(game_loop),
+                    died_at: None,
+                    transport: Some(unit_id.clone()),
+                });
+            }
+
+            let key = scoped_key(&unit_id, game_loop as i64);
+            let position_id = RecordId::from(("position", key.clone()));
+            self.records.positions.push(Position {
+                id: position_id.clone(),
+                game: game.clone(),
+                unit: unit_id.clone(),
+                game_loop,
+                observed_at: now.clone(),
+                x: pos.x,
+                y: pos.y,
+                z: pos.z,
+            });
+            self.records.has_position.push(HasPosition {
+                id: RecordId::from(("has_position", key.clone())),
+                unit: unit_id.clone(),
+                position: position_id,
+            });
+            self.records.unit_states.push(snapshot(
+                &unit,
+                RecordId::from(("unit_state", key)),
+                &game,
+                unit_id,
+                game_loop,
+            ));
+        }
+
+        let dead = event.map(|event| event.dead_units).unwrap_or_default();
+        let departures = presence.update(present, &dead);
+        self.records
+            .deaths
+            .extend(departures.dead.iter().map(|&tag| Death {
+                unit: unit_record(&game, tag),
+                died_at: game_loop,
+            }));
+        self.records
+            .fogged
+            .extend(departures.hidden.iter().map(|&tag| unit_record(&game, tag)));
+    }
+}
+
+/// State of `unit` at `game_loop`
+fn snapshot(
+    unit: &protocol::Unit,
+    id: RecordId,
+    game: &RecordId,
+    unit_id: RecordId,
+    game_loop: u32,
+) -> UnitState {
+    UnitState {
+        id,
+        version: UNIT_STATE_VERSION,
+        game: game.clone(),
+        unit: unit_id,
+        game_loop,
+        unit_type: unit.unit_type(),
+        display_type: unit.display_type().as_str_name().into(),
+        alliance: unit.alliance().as_str_name().into(),
+        owner: unit.owner(),
+        facing: unit.facing(),
+        build_progress: unit.build_progress(),
+        cloak: unit.cloak().as_str_name().into(),
+        buffs: unit.buff_ids.clone(),
+        health: unit.health(),
+        health_max: unit.health_max(),
+        shield: unit.shield(),
+        shield_max: unit.shield_max(),
+        energy: unit.energy(),
+        energy_max: unit.energy_max(),
+        mineral_contents: unit.mineral_contents(),
+        vespene_contents: unit.vespene_contents(),
+        assigned_harvesters: unit.assigned_harvesters(),
+        ideal_harvesters: unit.ideal_harvesters(),
+        weapon_cooldown: unit.weapon_cooldown(),
+        is_flying: unit.is_flying(),
+        is_burrowed: unit.is_burrowed(),
+        is_hallucination: unit.is_hallucination(),
+        is_powered: unit.is_powered(),
+        orders: unit
+            .orders
+            .iter()
+            .map(|order| {
+                let (target_unit, target_point) = match order.target {
+                    Some(Target::TargetUnitTag(tag)) => (Some(tag as i64), None),
+                    Some(Target::TargetWorldSpacePos(point)) => {
+                        (None, Some([point.x(), point.y()]))
+                    }
+                    None => (None, None),
+                };
+                Order {
+                    ability: order.ability_id(),
+                    progress: order.progress(),
+                    target_unit,
+                    target_point,
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::testing::{observation, snapshot_unit, unit};
+
+    #[test]
+    fn test_batch() {
+        let game = RecordId::from(("game", 1));
+        let mut presence = Presence::default();
+        let mut batch = Batch::new(game.clone());
+        batch.push(
+            1,
+            observation(vec![unit(1, 0.0), unit(2, 0.0), unit(3, 0.0)], vec![]),
+            &mut presence,
+        );
+        batch.push(
+            2,
+            observation(vec![snapshot_unit(1, 0.0)], vec![3]),
+            &mut presence,
+        );
+        batch.push(
+            3,
+            observation(vec![snapshot_unit(1, 0.0), unit(2, 0.0)], vec![]),
+            &mut presence,
+        );
+
+        // one record per unit, the last visible loop of unit 1 is kept
+        assert_eq!(batch.records.units.len(), 3);
+        assert_eq!(batch.records.units[0].status, UnitStatus::Fogged);
+        assert_eq!(batch.records.units[0].last_seen, Some(1));
+        assert_eq!(batch.records.units[1].last_seen, Some(3));
+        assert_eq!(batch.records.positions.len(), 6);
+        // unit 2 came back out of the fog within the batch
+        assert!(batch.records.fogged.is_empty());
+        assert_eq!(
+            batch.records.deaths,
+            [Death {
+                unit: unit_record(&game, 3),
+                died_at: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let game = RecordId::from(("game", 1));
+        let unit_id = unit_record(&game, 7);
+        let unit = protocol::Unit {
+            tag: Some(7),
+            unit_type: Some(45),
+            health: Some(40.0),
+            health_max: Some(45.0),
+            orders: vec![
+                protocol::UnitOrder {
+                    ability_id: Some(23),
+                    target: Some(Target::TargetUnitTag(9)),
+                    progress: None,
+                },
+                protocol::UnitOrder {
+                    ability_id: Some(16),
+                    target: Some(Target::TargetWorldSpacePos(protocol::Point {
+                        x: Some(1.0),
+                        y: Some(2.0),
+                        z: Some(0.0),
+                    })),
+                    progress: None,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let state = snapshot(&unit, RecordId::from(("unit_state", 1)), &game, unit_id, 22);
+        assert_eq!(state.version, UNIT_STATE_VERSION);
+        assert_eq!(state.game_loop, 22);
+        assert_eq!((state.health, state.health_max), (40.0, 45.0));
+        assert_eq!(state.alliance, "Self");
+        assert_eq!(state.orders[0].target_unit, Some(9));
+        assert_eq!(state.orders[1].target_point, Some([1.0, 2.0]));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+
+use surrealdb::{Datetime, RecordId};
+
+use super::{
+    Game, GameResult, GameStore, ObservationRecords, Player, Position, Unit, UnitState, UnitStatus,
+};
+
+/// Store keeping the records in memory, for runs without a database and for tests.
+///
+/// The `has_position` relations are not kept, positions are indexed by unit instead.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    games: HashMap<RecordId, (Game, Option<Datetime>)>,
+    players: Vec<Player>,
+    results: Vec<GameResult>,
+    units: HashMap<RecordId, Unit>,
+    positions: BTreeMap<(RecordId, u32), Position>,
+    unit_states: BTreeMap<(RecordId, u32), UnitState>,
+}
+
+impl MemoryStore {
+    pub fn game(&self, id: &RecordId) -> Option<&Game> {
+        self.games.get(id).map(|(game, _)| game)
+    }
+    pub fn ended_at(&self, game: &RecordId) -> Option<&Datetime> {
+        self.games
+            .get(game)
+            .and_then(|(_, ended_at)| ended_at.as_ref())
+    }
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+    pub fn results(&self) -> &[GameResult] {
+        &self.results
+    }
+    pub fn unit(&self, id: &RecordId) -> Option<&Unit> {
+        self.units.get(id)
+    }
+    /// Snapshots of the units, one per unit and game loop
+    pub fn unit_states(&self) -> impl ExactSizeIterator<Item = &UnitState> {
+        self.unit_states.values()
+    }
+}
+
+impl GameStore for MemoryStore {
+    async fn register_game(&mut self, game: Game, players: Vec<Player>) -> anyhow::Result<()> {
+        self.games.insert(game.id.clone(), (game, None));
+        self.players.extend(players);
+        Ok(())
+    }
+
+    async fn register_result(
+        &mut self,
+        game: RecordId,
+        ended_at: Datetime,
+        results: Vec<GameResult>,
+    ) -> anyhow::Result<()> {
+        if let Some((_, end)) = self.games.get_mut(&game) {
+            *end = Some(ended_at);
+        }
+        self.results.extend(results);
+        Ok(())
+    }
+
+    async fn register_observations(&mut self, records: ObservationRecords) -> anyhow::Result<()> {
+        for unit in records.units {
+            match self.units.get_mut(&unit.id) {
+                Some(stored) => {
+                    stored.unit_type = unit.unit_type;
+                    stored.status = unit.status;
+                    stored.last_seen = unit.last_seen.or(stored.last_seen);
+                    stored.transport = unit.transport;
+                }
+                None => {
+                    self.units.insert(unit.id.clone(), unit);
+                }
+            }
+        }
+        for position in records.positions {
+            let key = (position.unit.clone(), position.game_loop);
+            self.positions.insert(key, position);
+        }
+        // like `INSERT IGNORE`, a snapshot already recorded is kept
+        for state in records.unit_states {
+            let key = (state.unit.clone(), state.game_loop);
+            self.unit_states.entry(key).or_insert(state);
+        }
+        for death in records.deaths {
+            if let Some(unit) = self.units.get_mut(&death.unit) {
+                unit.status = UnitStatus::Dead;
+                unit.died_at = Some(death.died_at);
+                unit.transport = None;
+            }
+        }
+        for id in records.fogged {
+            if let Some(unit) = self.units.get_mut(&id) {
+                unit.status = UnitStatus::Fogged;
+                unit.transport = None;
+            }
+        }
+        Ok(())
+    }
+
+    async fn trajectory(
+        &self,
+        unit: RecordId,
+        loops: RangeInclusive<u32>,
+    ) -> anyhow::Result<Vec<Position>> {
+        let range = (unit.clone(), *loops.start())..=(unit, *loops.end());
+        Ok(self
+            .positions
+            .range(range)
+            .map(|(_, p)| p.clone())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rsc2::protocol;
+
+    use super::*;
+    use crate::store::{
+        World,
+        testing::{game_info, observation, unit},
+        unit_record,
+    };
+
+    #[tokio::test]
+    async fn test_world() {
+        let mut world = World::new(MemoryStore::default());
+        let game = world.start_game(&game_info()).await.unwrap();
+
+        world
+            .register_observations([
+                (1, observation(vec![unit(1, 0.0), unit(2, 0.0)], vec![])),
+                (2, observation(vec![unit(1, 1.0)], vec![2])),
+            ])
+            .await
+            .unwrap();
+        // a unit keeps its tag when it morphs, here into a sieged tank
+        let mut morphed = unit(1, 2.0);
+        morphed.unit_type = Some(32);
+        world
+            .register_observations([(3, observation(vec![morphed], vec![]))])
+            .await
+            .unwrap();
+
+        let trajectory = world.trajectory(1, 2..=3).await.unwrap();
+        let xs: Vec<_> = trajectory.iter().map(|position| position.x).collect();
+        assert_eq!(xs, [1.0, 2.0]);
+
+        let morphed = world.store().unit(&unit_record(&game, 1)).unwrap();
+        assert_eq!(morphed.unit_type, 32);
+        let dead = world.store().unit(&unit_record(&game, 2)).unwrap();
+        assert_eq!((dead.status, dead.died_at), (UnitStatus::Dead, Some(2)));
+        assert_eq!(world.store().unit_states().len(), 4);
+
+        world
+            .end_game(&[protocol::PlayerResult {
+                player_id: Some(1),
+                result: Some(protocol::Result::Victory as i32),
+            }])
+            .await
+            .unwrap();
+        let store = world.store();
+        assert_eq!(store.game(&game).unwrap().map_name, "EphemeronLE");
+        assert!(store.ended_at(&game).is_some());
+        assert_eq!(store.results()[0].result, "Victory");
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_observation() {
+        let mut world = World::new(MemoryStore::default());
+        world.start_game(&game_info()).await.unwrap();
+
+        // a snapshot already recorded is kept, as SurrealDB ignores the duplicate keys
+        for x in [0.0, 1.0] {
+            world
+                .register_observations([(1, observation(vec![unit(1, x), unit(2, 0.0)], vec![]))])
+                .await
+                .unwrap();
+        }
+        let states: Vec<_> = world
+            .store()
+            .unit_states()
+            .map(|state| (state.unit.clone(), state.game_loop))
+            .collect();
+        assert_eq!(states.len(), 2);
+        assert_ne!(states[0], states[1]);
+        assert_eq!(world.trajectory(1, 0..=1).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,144 @@
+//! Versioned schema of the SurrealDB store.
+//!
+//! Each migration runs once, in its own transaction, along with the `migration` record of its
+//! version. Migrations are applied in order and never edited once released, schema changes are
+//! added as a new migration instead.
+use std::collections::BTreeSet;
+
+use anyhow::Context;
+use surrealdb::{Connection, Surreal};
+
+/// A schema change, `source` is the SurrealQL run to apply it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub source: &'static str,
+}
+
+/// Every migration, ordered by version
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    name: "initial",
+    source: include_str!("migrations/0001_initial.surql"),
+}];
+
+const DEFINE_MIGRATION: &str = "
+DEFINE TABLE IF NOT EXISTS migration SCHEMAFULL;
+DEFINE FIELD IF NOT EXISTS name ON migration TYPE string;
+DEFINE FIELD IF NOT EXISTS applied_at ON migration TYPE datetime;
+";
+
+/// Runs `source` and records its version in one transaction
+fn transaction(source: &str) -> String {
+    format!(
+        "BEGIN TRANSACTION;\n{source}\n\
+         CREATE type::thing('migration', $version) SET name = $name, applied_at = time::now();\n\
+         COMMIT TRANSACTION;"
+    )
+}
+
+/// Versions recorded in the `migration` table
+async fn applied<C: Connection>(db: &Surreal<C>) -> anyhow::Result<BTreeSet<u32>> {
+    let mut response = db
+        .query("SELECT VALUE record::id(id) FROM migration")
+        .await?;
+    let versions: Vec<i64> = response.take(0)?;
+    versions
+        .into_iter()
+        .map(|version| {
+            u32::try_from(version).with_context(|| format!("Invalid migration version {version}"))
+        })
+        .collect()
+}
+
+/// Applies the migrations missing from the database and returns them.
+///
+/// With `dry_run` nothing is written and the migrations that would be applied are returned.
+pub async fn migrate<C: Connection>(
+    db: &Surreal<C>,
+    dry_run: bool,
+) -> anyhow::Result<Vec<&'static Migration>> {
+    if !dry_run {
+        db.query(DEFINE_MIGRATION).await?.check()?;
+    }
+    let applied = applied(db).await?;
+    let latest = MIGRATIONS.last().map_or(0, |migration| migration.version);
+    if let Some(unknown) = applied.iter().find(|version| **version > latest) {
+        anyhow::bail!(
+            "Database has migration {unknown} applied, this build only knows up to {latest}"
+        );
+    }
+
+    let pending: Vec<_> = MIGRATIONS
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect();
+    for migration in &pending {
+        let Migration {
+            version,
+            name,
+            source,
+        } = **migration;
+        surrealdb::syn::parse(source)
+            .with_context(|| format!("Parsing migration {version} ({name})"))?;
+        if dry_run {
+            continue;
+        }
+
+        db.query(transaction(source))
+            .bind(("version", version))
+            .bind(("name", name))
+            .await?
+            .check()
+            .with_context(|| format!("Applying migration {version} ({name})"))?;
+        log::info!("Applied migration {version} ({name})");
+    }
+    Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations() {
+        assert!(
+            MIGRATIONS
+                .windows(2)
+                .all(|pair| pair[0].version < pair[1].version),
+            "migrations are ordered by version"
+        );
+        surrealdb::syn::parse(DEFINE_MIGRATION).unwrap();
+        for migration in MIGRATIONS {
+            surrealdb::syn::parse(&transaction(migration.source)).unwrap();
+        }
+    }
+
+    #[cfg(feature = "embedded")]
+    #[tokio::test]
+    async fn test_migrate() {
+        use std::collections::BTreeMap;
+
+        let db = crate::store::connect("mem://", "test", "test", None)
+            .await
+            .unwrap();
+        let pending = migrate(&db, true).await.unwrap();
+        assert_eq!(pending.len(), MIGRATIONS.len());
+        // a dry run doesn't even define the migration table
+        let tables: Option<BTreeMap<String, String>> = db
+            .query("INFO FOR DB")
+            .await
+            .unwrap()
+            .take((0, "tables"))
+            .unwrap();
+        assert!(tables.unwrap_or_default().is_empty());
+
+        assert_eq!(migrate(&db, false).await.unwrap(), pending);
+        let versions = MIGRATIONS.iter().map(|migration| migration.version);
+        assert_eq!(applied(&db).await.unwrap(), versions.collect());
+
+        assert!(migrate(&db, false).await.unwrap().is_empty());
+        assert!(migrate(&db, true).await.unwrap().is_empty());
+    }
+}
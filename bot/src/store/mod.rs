@@ -1,94 +1,207 @@
+//! Storage of the observations of games.
+//!
+//! [`World`] turns observations into records and hands them to a [`GameStore`]: SurrealDB over
+//! the network or embedded (`embedded` and `rocksdb` features), or kept in memory. The schema
+//! of SurrealDB stores is versioned by the migrations of [`MIGRATIONS`].
+mod batch;
+mod memory;
+mod migration;
 mod model;
-use std::sync::Arc;
+mod presence;
+mod surreal;
+#[cfg(test)]
+mod testing;
+mod writer;
+use std::{future::Future, ops::RangeInclusive, str::FromStr};
 
 use anyhow::Context;
-use chrono::{DateTime, Utc};
-pub use model::{HasPosition, Position, Unit};
+use chrono::Utc;
+pub use model::{
+    Death, Game, GameResult, HasPosition, Order, Player, Position, UNIT_STATE_VERSION, Unit,
+    UnitState, UnitStatus,
+};
 
 use rsc2::protocol;
-use surrealdb::{RecordId, Surreal, Value, engine::remote::ws::Client};
+use surrealdb::{Datetime, RecordId, RecordIdKey, Uuid, Value};
 
-use crate::queries;
+use batch::Batch;
+pub use batch::ObservationRecords;
+pub use memory::MemoryStore;
+pub use migration::{MIGRATIONS, Migration, migrate};
+use presence::Presence;
+pub use surreal::{SurrealStore, connect};
+pub use writer::{Overflow, Writer, WriterOptions};
 
-pub struct World {
-    store: Arc<Surreal<Client>>,
+/// Backend storing the records built by [`World`]
+pub trait GameStore: Send + 'static {
+    fn register_game(
+        &mut self,
+        game: Game,
+        players: Vec<Player>,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    fn register_result(
+        &mut self,
+        game: RecordId,
+        ended_at: Datetime,
+        results: Vec<GameResult>,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    /// Writes the records of consecutive observations at once
+    fn register_observations(
+        &mut self,
+        records: ObservationRecords,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    /// Positions of `unit` between two game loops, inclusive, ordered by game loop
+    fn trajectory(
+        &self,
+        unit: RecordId,
+        loops: RangeInclusive<u32>,
+    ) -> impl Future<Output = anyhow::Result<Vec<Position>>> + Send;
 }
 
-trait ValueExt {
-    fn from_dt(dt: DateTime<Utc>) -> Value {
-        <Value as std::str::FromStr>::from_str(&format!("{}", surrealdb::Datetime::from(dt)))
-            .expect("passing surreal datetime that should be valid")
+/// Store selected at startup from an endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// `memory`, records are kept by a [`MemoryStore`]
+    Memory,
+    /// SurrealDB endpoint opened by [`SurrealStore::open`], `ws://` is assumed without a scheme
+    Surreal(String),
+}
+
+impl FromStr for Backend {
+    type Err = std::convert::Infallible;
+
+    fn from_str(endpoint: &str) -> Result<Self, Self::Err> {
+        Ok(match endpoint {
+            "memory" => Self::Memory,
+            _ if endpoint.contains("://") => Self::Surreal(endpoint.into()),
+            _ => Self::Surreal(format!("ws://{endpoint}")),
+        })
     }
 }
 
-impl ValueExt for Value {}
+/// Observations of a game, stored under the `game` record created by [`World::start_game`]
+pub struct World<S> {
+    store: S,
+    game: Option<RecordId>,
+    presence: Presence,
+}
+
+fn unit_record(game: &RecordId, tag: u64) -> RecordId {
+    RecordId::from(("unit", scoped_key(game, tag as i64)))
+}
+
+/// Key of a record scoped to another record, such as the units of a game since unit tags and
+/// player ids are reused between games
+fn scoped_key(scope: &RecordId, key: impl Into<RecordIdKey>) -> Vec<Value> {
+    vec![Value::from(scope.clone()), Value::from(key.into())]
+}
+
+impl<S: GameStore> World<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            game: None,
+            presence: Presence::default(),
+        }
+    }
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Creates the `game` record of a new game along with its players
+    pub async fn start_game(
+        &mut self,
+        game_info: &protocol::ResponseGameInfo,
+    ) -> anyhow::Result<RecordId> {
+        let id = RecordId::from(("game", Uuid::now_v7()));
+        let game = Game {
+            id: id.clone(),
+            map_name: game_info.map_name().into(),
+            local_map_path: game_info.local_map_path().into(),
+            started_at: Utc::now().into(),
+        };
+        let players: Vec<_> = game_info
+            .player_info
+            .iter()
+            .map(|info| Player {
+                id: RecordId::from(("player", scoped_key(&id, info.player_id() as i64))),
+                game: id.clone(),
+                player_id: info.player_id(),
+                player_type: info.r#type().as_str_name().into(),
+                race_requested: info.race_requested().as_str_name().into(),
+                race_actual: info.race_actual().as_str_name().into(),
+                difficulty: info
+                    .difficulty
+                    .is_some()
+                    .then(|| info.difficulty().as_str_name().into()),
+                name: info.player_name().into(),
+            })
+            .collect();
 
-impl World {
-    pub fn new(store: Arc<Surreal<Client>>) -> Self {
-        Self { store }
+        self.store.register_game(game, players).await?;
+
+        log::info!("Registered game {id}");
+        self.game = Some(id.clone());
+        self.presence.clear();
+        Ok(id)
     }
 
-    pub async fn register_observation_raw(
-        &self,
-        observation: protocol::ObservationRaw,
-    ) -> anyhow::Result<()> {
-        let now = Utc::now();
-
-        let protocol::ObservationRaw { units, .. } = observation;
-
-        let (unit_data, (position_data, has_position)): (
-            Vec<Unit>,
-            (Vec<Position>, Vec<HasPosition>),
-        ) = units
-            .into_iter()
-            .map(|unit| {
-                let unit_id = RecordId::from(("unit", unit.tag() as i64));
-                let unit_type = unit.unit_type();
-                let unit_data = Unit {
-                    id: unit_id.clone(),
-                    unit_type,
-                };
-
-                let position_id = RecordId::from((
-                    "position",
-                    vec![Value::from(unit_id.clone()), Value::from_dt(now)],
-                ));
-                let position_data = Position {
-                    id: position_id.clone(),
-                    x: unit.pos.as_ref().and_then(|p| p.x).unwrap_or(0.0),
-                    y: unit.pos.as_ref().and_then(|p| p.y).unwrap_or(0.0),
-                    z: unit.pos.as_ref().and_then(|p| p.z).unwrap_or(0.0),
-                };
-
-                let has_position = HasPosition {
-                    unit: unit_id,
-                    position: position_id,
-                };
-                (unit_data, (position_data, has_position))
+    /// Records the result of every player and the end time of the current game
+    pub async fn end_game(&mut self, results: &[protocol::PlayerResult]) -> anyhow::Result<()> {
+        let game = self.game.take().context("No game was started")?;
+        let results: Vec<_> = results
+            .iter()
+            .map(|result| {
+                let key = scoped_key(&game, result.player_id() as i64);
+                GameResult {
+                    id: RecordId::from(("result", key.clone())),
+                    game: game.clone(),
+                    player: RecordId::from(("player", key)),
+                    result: result.result().as_str_name().into(),
+                }
             })
-            .unzip();
-
-        let response = self
-            .store
-            .query(
-                queries::get("register_observation")
-                    .await
-                    .with_context(|| "Expect a register_observation to be present")?,
-            )
-            .bind(("upsert_unit", unit_data))
-            .bind(("upsert_position", position_data))
-            .bind(("upsert_has_position", has_position))
-            .await?;
-
-        let check = response.check();
-        if let Err(e) = check.as_ref() {
-            log::error!("Error inserting observation: {}", e);
+            .collect();
+
+        self.store
+            .register_result(game, Utc::now().into(), results)
+            .await
+    }
+
+    /// Records the units of consecutive observations in a single transaction, positions are
+    /// keyed by game loop so registering the same observation twice leaves a single position
+    /// per unit.
+    ///
+    /// Units reported dead get their `died_at` game loop, units no longer observed are marked as
+    /// fogged and passengers of transports as transported.
+    pub async fn register_observations(
+        &mut self,
+        observations: impl IntoIterator<Item = (u32, protocol::ObservationRaw)>,
+    ) -> anyhow::Result<()> {
+        let game = self
+            .game
+            .clone()
+            .context("Observation registered before the game was started")?;
+        let mut batch = Batch::new(game);
+        for (game_loop, observation) in observations {
+            batch.push(game_loop, observation, &mut self.presence);
         }
+        let records = batch.into_records();
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.store.register_observations(records).await
+    }
 
-        log::trace!(
-            "Observation insertion status: {:?}",
-            check.map(|_| "OK").unwrap_or("XX")
-        );
-        Ok(())
+    /// Positions of the unit `tag` of the current game between two game loops, inclusive
+    pub async fn trajectory(
+        &self,
+        tag: u64,
+        loops: RangeInclusive<u32>,
+    ) -> anyhow::Result<Vec<Position>> {
+        let game = self.game.as_ref().context("No game was started")?;
+        self.store.trajectory(unit_record(game, tag), loops).await
     }
 }
@@ -1,9 +1,42 @@
 use serde::{Deserialize, Serialize};
-use surrealdb::RecordId;
+use surrealdb::{Datetime, RecordId};
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct Game {
+    pub id: RecordId,
+    pub map_name: String,
+    pub local_map_path: String,
+    pub started_at: Datetime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Player {
+    pub id: RecordId,
+    pub game: RecordId,
+    pub player_id: u32,
+    pub player_type: String,
+    pub race_requested: String,
+    pub race_actual: String,
+    pub difficulty: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GameResult {
+    pub id: RecordId,
+    pub game: RecordId,
+    pub player: RecordId,
+    pub result: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Position {
     pub id: RecordId,
+    pub game: RecordId,
+    pub unit: RecordId,
+    pub game_loop: u32,
+    /// Wall clock time of the observation, only comparable within a game
+    pub observed_at: Datetime,
     pub x: f32,
     pub y: f32,
     pub z: f32,
@@ -11,14 +44,85 @@ pub struct Position {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HasPosition {
+    pub id: RecordId,
     #[serde(rename = "in")]
     pub unit: RecordId,
     #[serde(rename = "out")]
     pub position: RecordId,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Whether a unit is seen, and why not when it isn't
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnitStatus {
+    Visible,
+    /// Under the fog of war, either remembered as a snapshot or no longer observed
+    Fogged,
+    /// Carried by the unit in `transport`
+    Transported,
+    Dead,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Unit {
     pub id: RecordId,
+    pub game: RecordId,
     pub unit_type: u32,
+    pub status: UnitStatus,
+    /// Game loop the unit was last visible at, `None` if only seen as a snapshot so far
+    pub last_seen: Option<u32>,
+    pub died_at: Option<u32>,
+    pub transport: Option<RecordId>,
+}
+
+/// Layout of [`UnitState`] records, bumped whenever fields change meaning
+pub const UNIT_STATE_VERSION: u32 = 1;
+
+/// State of a unit at a game loop
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct UnitState {
+    pub id: RecordId,
+    pub version: u32,
+    pub game: RecordId,
+    pub unit: RecordId,
+    pub game_loop: u32,
+    pub unit_type: u32,
+    pub display_type: String,
+    pub alliance: String,
+    pub owner: i32,
+    pub facing: f32,
+    pub build_progress: f32,
+    pub cloak: String,
+    pub buffs: Vec<u32>,
+    pub health: f32,
+    pub health_max: f32,
+    pub shield: f32,
+    pub shield_max: f32,
+    pub energy: f32,
+    pub energy_max: f32,
+    pub mineral_contents: i32,
+    pub vespene_contents: i32,
+    pub assigned_harvesters: i32,
+    pub ideal_harvesters: i32,
+    pub weapon_cooldown: f32,
+    pub is_flying: bool,
+    pub is_burrowed: bool,
+    pub is_hallucination: bool,
+    pub is_powered: bool,
+    pub orders: Vec<Order>,
+}
+
+/// Order of a unit, targets either a unit or a point
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Order {
+    pub ability: u32,
+    pub progress: f32,
+    pub target_unit: Option<i64>,
+    pub target_point: Option<[f32; 2]>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Death {
+    pub unit: RecordId,
+    pub died_at: u32,
 }
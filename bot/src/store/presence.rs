@@ -0,0 +1,61 @@
+use std::collections::HashSet;
+
+/// Units that left the observation since the previous one
+#[derive(Debug, Default, PartialEq)]
+pub struct Departures {
+    /// Reported dead by the observation event
+    pub dead: Vec<u64>,
+    /// No longer observed without being reported dead, gone into the fog of war or into a
+    /// building such as a refinery
+    pub hidden: Vec<u64>,
+}
+
+/// Units present in the previous observation, visible or carried in a transport
+#[derive(Debug, Default)]
+pub struct Presence {
+    present: HashSet<u64>,
+}
+
+impl Presence {
+    /// Replaces the present units with `present`, returning the units that died or disappeared
+    pub fn update(&mut self, present: HashSet<u64>, dead: &[u64]) -> Departures {
+        let hidden = self
+            .present
+            .difference(&present)
+            .filter(|tag| !dead.contains(tag))
+            .copied()
+            .collect();
+        self.present = present;
+        Departures {
+            dead: dead.to_vec(),
+            hidden,
+        }
+    }
+    pub fn clear(&mut self) {
+        self.present.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_departures() {
+        let mut presence = Presence::default();
+        assert_eq!(
+            presence.update(HashSet::from([1, 2, 3]), &[]),
+            Departures::default()
+        );
+
+        let departures = presence.update(HashSet::from([1]), &[2]);
+        assert_eq!(departures.dead, [2]);
+        assert_eq!(departures.hidden, [3]);
+
+        // hidden units are only reported once
+        assert_eq!(
+            presence.update(HashSet::from([1]), &[]),
+            Departures::default()
+        );
+    }
+}
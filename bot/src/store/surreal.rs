@@ -0,0 +1,250 @@
+use std::ops::RangeInclusive;
+
+use anyhow::Context;
+use serde::Serialize;
+use surrealdb::{
+    Connection, Datetime, RecordId, Surreal, engine::any::Any, method, opt::auth::Root,
+};
+
+use super::{Game, GameResult, GameStore, ObservationRecords, Player, Position, migrate};
+use crate::queries::Query;
+
+/// Store backed by a SurrealDB database, remote or embedded depending on `C`
+pub struct SurrealStore<C: Connection> {
+    db: Surreal<C>,
+}
+
+impl<C: Connection> SurrealStore<C> {
+    /// Migrates the schema of the namespace and database selected on `db`, see [`migrate`]
+    pub async fn new(db: Surreal<C>) -> anyhow::Result<Self> {
+        Query::check_all()?;
+        migrate(&db, false).await?;
+        log::info!("SurrealDB schema up to date");
+        Ok(Self { db })
+    }
+
+    fn query(&self, query: Query) -> Bound<'_, C> {
+        Bound {
+            query,
+            inner: self.db.query(query.source()),
+            bound: Vec::new(),
+        }
+    }
+}
+
+/// Query of the store, the parameters bound are checked against [`Query::params`]
+struct Bound<'r, C: Connection> {
+    query: Query,
+    inner: method::Query<'r, C>,
+    bound: Vec<&'static str>,
+}
+
+impl<C: Connection> Bound<'_, C> {
+    fn bind(mut self, name: &'static str, value: impl Serialize + 'static) -> Self {
+        let query = self.query;
+        assert!(
+            query.params().contains(&name),
+            "${name} is not a parameter of the {query} query"
+        );
+        self.bound.push(name);
+        self.inner = self.inner.bind((name, value));
+        self
+    }
+
+    async fn run(self) -> surrealdb::Result<surrealdb::Response> {
+        let query = self.query;
+        let unbound: Vec<_> = query
+            .params()
+            .iter()
+            .filter(|param| !self.bound.contains(param))
+            .collect();
+        assert!(
+            unbound.is_empty(),
+            "parameters {unbound:?} of the {query} query are not bound"
+        );
+        self.inner.await
+    }
+}
+
+/// Connects to the database at `endpoint`, `ws://host:port` for a server, `mem://` or
+/// `rocksdb://<path>` for an embedded database.
+///
+/// Embedded engines need the `embedded` (`mem://`) or `rocksdb` feature.
+pub async fn connect(
+    endpoint: &str,
+    namespace: &str,
+    database: &str,
+    credentials: Option<Root<'_>>,
+) -> anyhow::Result<Surreal<Any>> {
+    let db = surrealdb::engine::any::connect(endpoint)
+        .await
+        .with_context(|| format!("Opening SurrealDB at {endpoint}"))?;
+    if let Some(credentials) = credentials {
+        db.signin(credentials)
+            .await
+            .with_context(|| format!("Signing in to {endpoint} as {}", credentials.username))?;
+    }
+    db.use_ns(namespace).use_db(database).await?;
+    log::info!("Game state connection initialized");
+    Ok(db)
+}
+
+impl SurrealStore<Any> {
+    /// Connects to `endpoint` and migrates its schema, see [`connect`]
+    pub async fn open(
+        endpoint: &str,
+        namespace: &str,
+        database: &str,
+        credentials: Option<Root<'_>>,
+    ) -> anyhow::Result<Self> {
+        Self::new(connect(endpoint, namespace, database, credentials).await?).await
+    }
+}
+
+impl<C: Connection> GameStore for SurrealStore<C> {
+    async fn register_game(&mut self, game: Game, players: Vec<Player>) -> anyhow::Result<()> {
+        self.query(Query::RegisterGame)
+            .bind("game", game)
+            .bind("players", players)
+            .run()
+            .await?
+            .check()?;
+        Ok(())
+    }
+
+    async fn register_result(
+        &mut self,
+        game: RecordId,
+        ended_at: Datetime,
+        results: Vec<GameResult>,
+    ) -> anyhow::Result<()> {
+        self.query(Query::RegisterResult)
+            .bind("game", game)
+            .bind("ended_at", ended_at)
+            .bind("results", results)
+            .run()
+            .await?
+            .check()?;
+        Ok(())
+    }
+
+    async fn register_observations(&mut self, records: ObservationRecords) -> anyhow::Result<()> {
+        let ObservationRecords {
+            units,
+            positions,
+            has_position,
+            unit_states,
+            deaths,
+            fogged,
+        } = records;
+
+        self.query(Query::RegisterObservation)
+            .bind("upsert_unit", units)
+            .bind("upsert_position", positions)
+            .bind("upsert_has_position", has_position)
+            .bind("upsert_unit_state", unit_states)
+            .bind("dead_units", deaths)
+            .bind("fogged_units", fogged)
+            .run()
+            .await?
+            .check()?;
+        Ok(())
+    }
+
+    async fn trajectory(
+        &self,
+        unit: RecordId,
+        loops: RangeInclusive<u32>,
+    ) -> anyhow::Result<Vec<Position>> {
+        let mut response = self
+            .query(Query::Trajectory)
+            .bind("unit", unit)
+            .bind("from", *loops.start())
+            .bind("to", *loops.end())
+            .run()
+            .await?;
+        Ok(response.take(0)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> SurrealStore<Any> {
+        SurrealStore {
+            db: Surreal::init(),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "$units is not a parameter of the register_observation query")]
+    fn test_unknown_param() {
+        let store = store();
+        let _ = store.query(Query::RegisterObservation).bind("units", 0);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "[\"to\"] of the trajectory query are not bound")]
+    async fn test_unbound_param() {
+        let store = store();
+        let query = store
+            .query(Query::Trajectory)
+            .bind("unit", 0)
+            .bind("from", 0);
+        let _ = query.run().await;
+    }
+
+    /// Runs every query of the store against an embedded database
+    #[cfg(feature = "embedded")]
+    #[tokio::test]
+    async fn test_mem_store() {
+        use crate::store::{
+            GameResult, Unit, UnitStatus, World,
+            testing::{game_info, observation, unit},
+            unit_record,
+        };
+        use rsc2::protocol;
+
+        let store = SurrealStore::open("mem://", "test", "test", None)
+            .await
+            .unwrap();
+        let mut world = World::new(store);
+        let game = world.start_game(&game_info()).await.unwrap();
+        world
+            .register_observations([
+                (1, observation(vec![unit(1, 0.0), unit(2, 0.0)], vec![])),
+                (2, observation(vec![unit(1, 1.0)], vec![2])),
+            ])
+            .await
+            .unwrap();
+        // a unit keeps its tag when it morphs, here into a sieged tank
+        let mut morphed = unit(1, 2.0);
+        morphed.unit_type = Some(32);
+        world
+            .register_observations([(3, observation(vec![morphed], vec![]))])
+            .await
+            .unwrap();
+        let trajectory = world.trajectory(1, 2..=3).await.unwrap();
+        let xs: Vec<_> = trajectory.iter().map(|position| position.x).collect();
+        assert_eq!(xs, [1.0, 2.0]);
+
+        world
+            .end_game(&[protocol::PlayerResult {
+                player_id: Some(1),
+                result: Some(protocol::Result::Victory as i32),
+            }])
+            .await
+            .unwrap();
+
+        let db = &world.store().db;
+        let morphed: Option<Unit> = db.select(unit_record(&game, 1)).await.unwrap();
+        assert_eq!(morphed.unwrap().unit_type, 32);
+        let dead: Option<Unit> = db.select(unit_record(&game, 2)).await.unwrap();
+        let dead = dead.unwrap();
+        assert_eq!((dead.status, dead.died_at), (UnitStatus::Dead, Some(2)));
+        let results: Vec<GameResult> = db.select("result").await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].result, "Victory");
+    }
+}
@@ -0,0 +1,45 @@
+//! Observations shared by the tests of the store.
+use rsc2::protocol;
+
+/// Visible unit at `(x, 0, 0)`
+pub fn unit(tag: u64, x: f32) -> protocol::Unit {
+    let mut unit = protocol::Unit {
+        tag: Some(tag),
+        unit_type: Some(45),
+        pos: Some(protocol::Point {
+            x: Some(x),
+            y: Some(0.0),
+            z: Some(0.0),
+        }),
+        ..Default::default()
+    };
+    unit.set_display_type(protocol::DisplayType::Visible);
+    unit
+}
+
+/// Unit in the fog of war at `(x, 0, 0)`, as last seen
+pub fn snapshot_unit(tag: u64, x: f32) -> protocol::Unit {
+    let mut unit = unit(tag, x);
+    unit.set_display_type(protocol::DisplayType::Snapshot);
+    unit
+}
+
+pub fn observation(units: Vec<protocol::Unit>, dead: Vec<u64>) -> protocol::ObservationRaw {
+    protocol::ObservationRaw {
+        units,
+        event: Some(protocol::Event { dead_units: dead }),
+        ..Default::default()
+    }
+}
+
+/// Game of a single player with id `1`
+pub fn game_info() -> protocol::ResponseGameInfo {
+    protocol::ResponseGameInfo {
+        map_name: Some("EphemeronLE".into()),
+        player_info: vec![protocol::PlayerInfo {
+            player_id: Some(1),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
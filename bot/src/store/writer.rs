@@ -0,0 +1,357 @@
+use rsc2::protocol;
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+
+use super::{GameStore, World};
+
+/// What to do with an observation when the writer is `capacity` observations behind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait for the writer to catch up, the game loop is slowed down by the store
+    #[default]
+    Block,
+    /// Drop the observation, the game loop never waits for the store. The units reported dead
+    /// by a dropped observation are recorded with the next observation written.
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WriterOptions {
+    /// Messages queued before `overflow` applies
+    pub capacity: usize,
+    /// Most observations written in one transaction
+    pub batch_size: usize,
+    pub overflow: Overflow,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            batch_size: 16,
+            overflow: Overflow::Block,
+        }
+    }
+}
+
+enum Message {
+    StartGame(
+        Box<protocol::ResponseGameInfo>,
+        oneshot::Sender<anyhow::Result<()>>,
+    ),
+    Observation(u32, Box<protocol::ObservationRaw>),
+    EndGame(
+        Vec<protocol::PlayerResult>,
+        oneshot::Sender<anyhow::Result<()>>,
+    ),
+}
+
+/// Writes to a [`World`] from a background task.
+///
+/// Observations are queued on a bounded channel and the ones waiting when the task wakes up are
+/// written together in a single transaction.
+pub struct Writer {
+    sender: mpsc::Sender<Message>,
+    overflow: Overflow,
+    dropped: usize,
+    /// Last dropped observation, carrying the dead units of every observation dropped since the
+    /// last one queued
+    pending: Option<(u32, protocol::ObservationRaw)>,
+    task: JoinHandle<()>,
+}
+
+impl Writer {
+    pub fn spawn<S: GameStore>(world: World<S>, options: WriterOptions) -> Self {
+        let (sender, receiver) = mpsc::channel(options.capacity.max(1));
+        Self {
+            sender,
+            overflow: options.overflow,
+            dropped: 0,
+            pending: None,
+            task: tokio::spawn(run(world, receiver, options.batch_size.max(1))),
+        }
+    }
+
+    async fn send(&self, message: Message) -> anyhow::Result<()> {
+        self.sender
+            .send(message)
+            .await
+            .map_err(|_| anyhow::anyhow!("Store writer stopped"))
+    }
+
+    /// Creates the game record, waiting for it to be written
+    pub async fn start_game(&self, game_info: protocol::ResponseGameInfo) -> anyhow::Result<()> {
+        let (reply, done) = oneshot::channel();
+        self.send(Message::StartGame(Box::new(game_info), reply))
+            .await?;
+        done.await?
+    }
+
+    /// Queues an observation following the overflow policy
+    pub async fn observation(
+        &mut self,
+        game_loop: u32,
+        mut observation: protocol::ObservationRaw,
+    ) -> anyhow::Result<()> {
+        if let Some((_, dropped)) = self.pending.take() {
+            carry_dead_units(dropped, &mut observation);
+        }
+        let message = Message::Observation(game_loop, Box::new(observation));
+        match self.overflow {
+            Overflow::Block => self.send(message).await,
+            Overflow::Drop => match self.sender.try_send(message) {
+                Ok(()) => Ok(()),
+                Err(mpsc::error::TrySendError::Full(message)) => {
+                    let Message::Observation(game_loop, observation) = message else {
+                        unreachable!("an observation was sent")
+                    };
+                    self.pending = Some((game_loop, *observation));
+                    self.dropped += 1;
+                    log::warn!(
+                        "Store writer is behind, dropped observation of game loop {game_loop} \
+                         ({} dropped)",
+                        self.dropped
+                    );
+                    Ok(())
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => {
+                    Err(anyhow::anyhow!("Store writer stopped"))
+                }
+            },
+        }
+    }
+
+    /// Records the results once every queued observation is written, the last observation
+    /// dropped since then is written first so the units it reported dead are recorded
+    pub async fn end_game(&mut self, results: Vec<protocol::PlayerResult>) -> anyhow::Result<()> {
+        if let Some((game_loop, observation)) = self.pending.take() {
+            self.send(Message::Observation(game_loop, Box::new(observation)))
+                .await?;
+        }
+        let (reply, done) = oneshot::channel();
+        self.send(Message::EndGame(results, reply)).await?;
+        done.await?
+    }
+
+    /// Observations dropped by [`Overflow::Drop`]
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Writes what is left in the queue and stops the task
+    pub async fn close(self) -> anyhow::Result<()> {
+        drop(self.sender);
+        Ok(self.task.await?)
+    }
+}
+
+/// Adds the units reported dead by a `dropped` observation to the ones of `observation`
+fn carry_dead_units(dropped: protocol::ObservationRaw, observation: &mut protocol::ObservationRaw) {
+    let Some(event) = dropped.event else { return };
+    if event.dead_units.is_empty() {
+        return;
+    }
+    let dead_units = &mut observation.event.get_or_insert_default().dead_units;
+    let mut carried = event.dead_units;
+    carried.retain(|tag| !dead_units.contains(tag));
+    dead_units.extend(carried);
+}
+
+async fn run<S: GameStore>(
+    mut world: World<S>,
+    mut receiver: mpsc::Receiver<Message>,
+    batch_size: usize,
+) {
+    let mut messages = Vec::with_capacity(batch_size);
+    let mut observations = Vec::with_capacity(batch_size);
+    while receiver.recv_many(&mut messages, batch_size).await > 0 {
+        for message in messages.drain(..) {
+            match message {
+                Message::Observation(game_loop, observation) => {
+                    observations.push((game_loop, *observation));
+                }
+                Message::StartGame(game_info, reply) => {
+                    flush(&mut world, &mut observations).await;
+                    let _ = reply.send(world.start_game(&game_info).await.map(|_| ()));
+                }
+                Message::EndGame(results, reply) => {
+                    flush(&mut world, &mut observations).await;
+                    let _ = reply.send(world.end_game(&results).await);
+                }
+            }
+        }
+        flush(&mut world, &mut observations).await;
+    }
+}
+
+async fn flush<S: GameStore>(
+    world: &mut World<S>,
+    observations: &mut Vec<(u32, protocol::ObservationRaw)>,
+) {
+    if observations.is_empty() {
+        return;
+    }
+    let count = observations.len();
+    if let Err(e) = world.register_observations(observations.drain(..)).await {
+        log::error!("Error writing {count} observations: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ops::RangeInclusive, sync::Arc};
+
+    use rsc2::{
+        Agent, Core, StepMode,
+        action::Actions,
+        agent::play,
+        connect_s2api,
+        testing::{fake_api, fake_step_game},
+    };
+    use surrealdb::{Datetime, RecordId};
+    use tokio::sync::Mutex;
+
+    use super::*;
+    use crate::store::{
+        Game, GameResult, MemoryStore, ObservationRecords, Player, Position, UnitStatus,
+        testing::{game_info, observation, unit},
+        unit_record,
+    };
+
+    /// Memory store still readable once moved into the writer
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<MemoryStore>>);
+
+    impl GameStore for Shared {
+        async fn register_game(&mut self, game: Game, players: Vec<Player>) -> anyhow::Result<()> {
+            self.0.lock().await.register_game(game, players).await
+        }
+        async fn register_result(
+            &mut self,
+            game: RecordId,
+            ended_at: Datetime,
+            results: Vec<GameResult>,
+        ) -> anyhow::Result<()> {
+            self.0
+                .lock()
+                .await
+                .register_result(game, ended_at, results)
+                .await
+        }
+        async fn register_observations(
+            &mut self,
+            records: ObservationRecords,
+        ) -> anyhow::Result<()> {
+            self.0.lock().await.register_observations(records).await
+        }
+        async fn trajectory(
+            &self,
+            unit: RecordId,
+            loops: RangeInclusive<u32>,
+        ) -> anyhow::Result<Vec<Position>> {
+            self.0.lock().await.trajectory(unit, loops).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop() {
+        let store = Shared::default();
+        let options = WriterOptions {
+            capacity: 1,
+            batch_size: 16,
+            overflow: Overflow::Drop,
+        };
+        let mut writer = Writer::spawn(World::new(store.clone()), options);
+        writer.start_game(game_info()).await.unwrap();
+
+        // the writer task doesn't run until the test awaits, only the first observation fits
+        let units = vec![unit(1, 0.0), unit(2, 0.0), unit(3, 0.0)];
+        writer
+            .observation(1, observation(units, vec![]))
+            .await
+            .unwrap();
+        let units = vec![unit(1, 1.0), unit(3, 0.0)];
+        writer
+            .observation(2, observation(units, vec![2]))
+            .await
+            .unwrap();
+        writer
+            .observation(3, observation(vec![unit(1, 2.0)], vec![]))
+            .await
+            .unwrap();
+        assert_eq!(writer.dropped(), 2);
+
+        let results = vec![protocol::PlayerResult {
+            player_id: Some(1),
+            result: Some(protocol::Result::Victory as i32),
+        }];
+        writer.end_game(results).await.unwrap();
+        {
+            // written before the results were
+            let store = store.0.lock().await;
+            let game = store.results()[0].game.clone();
+            let unit = |tag| store.unit(&unit_record(&game, tag)).unwrap();
+            assert_eq!(
+                (unit(1).status, unit(1).last_seen),
+                (UnitStatus::Visible, Some(3))
+            );
+            // reported dead by a dropped observation
+            assert_eq!(
+                (unit(2).status, unit(2).died_at),
+                (UnitStatus::Dead, Some(3))
+            );
+            assert_eq!(unit(3).status, UnitStatus::Fogged);
+            assert_eq!(store.unit_states().len(), 4);
+        }
+        writer.close().await.unwrap();
+    }
+
+    /// Records the game and its results like the bot does
+    struct Recorder(Writer);
+
+    impl Agent for Recorder {
+        async fn on_start(
+            &mut self,
+            game_info: &protocol::ResponseGameInfo,
+            _: &protocol::ResponseData,
+        ) {
+            self.0.start_game(game_info.clone()).await.unwrap();
+        }
+        async fn on_step(&mut self, _: &protocol::ResponseObservation) -> Actions {
+            Actions::new()
+        }
+        async fn on_end(&mut self, results: &[protocol::PlayerResult]) {
+            self.0.end_game(results.to_vec()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_step_ended_results() {
+        let (addr, _server) = fake_api(fake_step_game(2)).await;
+        let mut connection = connect_s2api(addr).await.unwrap();
+        let mut core = Core::init();
+        let state = core.launched().unwrap();
+        let state = state
+            .join_game(&mut connection, Default::default())
+            .await
+            .unwrap();
+
+        let store = Shared::default();
+        let writer = Writer::spawn(World::new(store.clone()), WriterOptions::default());
+        let mut agent = Recorder(writer);
+        play(state, &mut connection, StepMode::Step(1), &mut agent)
+            .await
+            .unwrap();
+        agent.0.close().await.unwrap();
+
+        let store = store.0.lock().await;
+        let results: Vec<_> = store
+            .results()
+            .iter()
+            .map(|result| result.result.as_str())
+            .collect();
+        assert_eq!(results, ["Victory"]);
+    }
+}
@@ -5,6 +5,12 @@ pub struct RollingRecorder<const SIZE: usize> {
     index: usize,
 }
 
+impl<const SIZE: usize> Default for RollingRecorder<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<const SIZE: usize> RollingRecorder<SIZE> {
     pub const fn new() -> Self {
         Self {
@@ -3,6 +3,7 @@
 //! Currently implements:
 //!
 //! * Rust code generated from the protobuf api.
+//! * Conversions from every request message into a [`protocol::Request`].
 //! * Codec to be used alongside a websocket client (uses [`websocket_codec`](crate::websocket_codec)
 //!   under the hood).
 //!
@@ -14,11 +15,50 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 /// Generated protobuf protocol
-#[allow(missing_docs)] // TODO: add custom documention for protocol
+#[allow(missing_docs, clippy::all)] // TODO: add custom documention for protocol
 pub mod protocol {
     include!(concat!(env!("OUT_DIR"), "/sc2api_protocol.rs"));
 }
 
+macro_rules! impl_request_from {
+    {$($variant:ident => $request:ident),+} => {
+        $(
+        impl From<protocol::$request> for protocol::Request {
+            fn from(item: protocol::$request) -> Self {
+                Self {
+                    id: None,
+                    request: Some(protocol::request::Request::$variant(item)),
+                }
+            }
+        }
+        )*
+    };
+}
+impl_request_from! {
+    CreateGame => RequestCreateGame,
+    JoinGame => RequestJoinGame,
+    RestartGame => RequestRestartGame,
+    StartReplay => RequestStartReplay,
+    LeaveGame => RequestLeaveGame,
+    QuickSave => RequestQuickSave,
+    QuickLoad => RequestQuickLoad,
+    Quit => RequestQuit,
+    GameInfo => RequestGameInfo,
+    Observation => RequestObservation,
+    Action => RequestAction,
+    ObsAction => RequestObserverAction,
+    Step => RequestStep,
+    Data => RequestData,
+    Query => RequestQuery,
+    SaveReplay => RequestSaveReplay,
+    MapCommand => RequestMapCommand,
+    ReplayInfo => RequestReplayInfo,
+    AvailableMaps => RequestAvailableMaps,
+    SaveMap => RequestSaveMap,
+    Ping => RequestPing,
+    Debug => RequestDebug
+}
+
 #[cfg(feature = "codec")]
 #[cfg_attr(docsrs, doc(cfg(feature = "codec")))]
 pub mod codec {
@@ -34,7 +74,8 @@ pub mod codec {
 
     /// Client codec to interact with a SC2 instance
     ///
-    /// This instance keeps track of the request id, custom ids will be overritten.
+    /// This instance keeps track of the request id, typed requests and requests sent without an
+    /// id are given the next one.
     pub struct S2Codec {
         id: u32,
         inner: MessageCodec,
@@ -79,10 +120,16 @@ pub mod codec {
         type Error = io::Error;
         fn encode(
             &mut self,
-            item: protocol::Request,
+            mut item: protocol::Request,
             dst: &mut BytesMut,
         ) -> Result<(), Self::Error> {
-            self.id = item.id();
+            match item.id {
+                Some(id) => self.id = id,
+                None => {
+                    self.id = self.id.wrapping_add(1);
+                    item.id = Some(self.id);
+                }
+            }
             let mut buffer = BytesMut::with_capacity(item.encoded_len());
             item.encode(&mut buffer)?;
             match self.inner.encode(Message::binary(buffer), dst) {
@@ -92,7 +139,7 @@ pub mod codec {
         }
     }
     macro_rules! impl_req_encoder {
-        {$($variant:ident => $request:ident),+} => {
+        {$($request:ident),+} => {
             $(
             impl Encoder<$crate::protocol::$request> for S2Codec {
                 type Error = io::Error;
@@ -101,46 +148,35 @@ pub mod codec {
                     item: $crate::protocol::$request,
                     dst: &mut ::bytes::BytesMut,
                 ) -> Result<(), Self::Error> {
-                    self.id = self.id.wrapping_add(1);
-                    let request = $crate::protocol::Request {
-                        id: Some(self.id),
-                        request: Some(
-                            $crate::protocol::request::Request::$variant(item),
-                        ),
-                    };
-                    let mut buffer = ::bytes::BytesMut::with_capacity(request.encoded_len());
-                    request.encode(&mut buffer)?;
-                    match self.inner.encode(Message::binary(buffer), dst) {
-                        Ok(()) => Ok(()),
-                        Err(e) => Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData, e)),
-                    }
+                    // wrapped without an id, the next one is assigned
+                    self.encode($crate::protocol::Request::from(item), dst)
                 }
             }
             )*
         };
     }
     impl_req_encoder! {
-        CreateGame => RequestCreateGame,
-        JoinGame => RequestJoinGame,
-        RestartGame => RequestRestartGame,
-        StartReplay => RequestStartReplay,
-        LeaveGame => RequestLeaveGame,
-        QuickSave => RequestQuickSave,
-        QuickLoad => RequestQuickLoad,
-        Quit => RequestQuit,
-        GameInfo => RequestGameInfo,
-        Observation => RequestObservation,
-        Action => RequestAction,
-        ObsAction => RequestObserverAction,
-        Step => RequestStep,
-        Data => RequestData,
-        Query => RequestQuery,
-        SaveReplay => RequestSaveReplay,
-        MapCommand => RequestMapCommand,
-        ReplayInfo => RequestReplayInfo,
-        AvailableMaps => RequestAvailableMaps,
-        SaveMap => RequestSaveMap,
-        Ping => RequestPing,
-        Debug => RequestDebug
+        RequestCreateGame,
+        RequestJoinGame,
+        RequestRestartGame,
+        RequestStartReplay,
+        RequestLeaveGame,
+        RequestQuickSave,
+        RequestQuickLoad,
+        RequestQuit,
+        RequestGameInfo,
+        RequestObservation,
+        RequestAction,
+        RequestObserverAction,
+        RequestStep,
+        RequestData,
+        RequestQuery,
+        RequestSaveReplay,
+        RequestMapCommand,
+        RequestReplayInfo,
+        RequestAvailableMaps,
+        RequestSaveMap,
+        RequestPing,
+        RequestDebug
     }
 }
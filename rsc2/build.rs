@@ -0,0 +1,205 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::Path;
+
+use serde_json::Value;
+
+const ENTITIES: &str = "entities.json";
+
+struct Entity<'a> {
+    id: u64,
+    name: &'a str,
+    race: Option<&'a str>,
+}
+
+fn entities(data: &Value, kind: &str) -> Vec<Value> {
+    data[kind].as_array().cloned().unwrap_or_default()
+}
+
+/// Race shared by every unit in `races`, `None` if it is used by several races
+fn single_race<'a>(races: Option<&BTreeSet<&'a str>>) -> Option<&'a str> {
+    races
+        .filter(|races| races.len() == 1)
+        .and_then(|races| races.first().copied())
+}
+
+fn to_entities<'a>(
+    values: &'a [Value],
+    race: impl Fn(&'a Value, u64) -> Option<&'a str>,
+) -> Vec<Entity<'a>> {
+    values
+        .iter()
+        .map(|value| {
+            let id = value["id"].as_u64().unwrap();
+            Entity {
+                id,
+                name: value["name"].as_str().unwrap(),
+                race: race(value, id),
+            }
+        })
+        .collect()
+}
+
+fn write_enum(out: &mut String, name: &str, kind: &str, entities: &[Entity], race: bool) {
+    writeln!(out, "/// {kind} ids of `entities.json`").unwrap();
+    writeln!(
+        out,
+        "#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "#[allow(non_camel_case_types, clippy::upper_case_acronyms)]"
+    )
+    .unwrap();
+    writeln!(out, "#[repr(u32)]\npub enum {name} {{").unwrap();
+    for entity in entities {
+        writeln!(out, "    {} = {},", entity.name, entity.id).unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(out, "impl {name} {{").unwrap();
+    writeln!(out, "    pub const ALL: &'static [Self] = &[").unwrap();
+    for entity in entities {
+        writeln!(out, "        Self::{},", entity.name).unwrap();
+    }
+    writeln!(out, "    ];").unwrap();
+
+    writeln!(out, "    /// Name of the {kind} in `entities.json`").unwrap();
+    writeln!(
+        out,
+        "    pub const fn name(self) -> &'static str {{\n        match self {{"
+    )
+    .unwrap();
+    for entity in entities {
+        writeln!(out, "            Self::{0} => \"{0}\",", entity.name).unwrap();
+    }
+    writeln!(out, "        }}\n    }}").unwrap();
+
+    if race {
+        writeln!(
+            out,
+            "    pub const fn race(self) -> Race {{\n        match self {{"
+        )
+        .unwrap();
+        for entity in entities {
+            let race = entity.race.unwrap_or("NoRace");
+            writeln!(out, "            Self::{} => Race::{},", entity.name, race).unwrap();
+        }
+    } else {
+        writeln!(
+            out,
+            "    /// Race of the units using this {kind}, `None` if it is shared by several races"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    pub const fn race(self) -> Option<Race> {{\n        match self {{"
+        )
+        .unwrap();
+        for entity in entities {
+            match entity.race {
+                Some(race) => {
+                    writeln!(
+                        out,
+                        "            Self::{} => Some(Race::{}),",
+                        entity.name, race
+                    )
+                }
+                None => writeln!(out, "            Self::{} => None,", entity.name),
+            }
+            .unwrap();
+        }
+    }
+    writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+
+    writeln!(out, "impl TryFrom<u32> for {name} {{").unwrap();
+    writeln!(out, "    type Error = Error;").unwrap();
+    writeln!(
+        out,
+        "    fn try_from(id: u32) -> Result<Self, Self::Error> {{"
+    )
+    .unwrap();
+    writeln!(out, "        match id {{").unwrap();
+    for entity in entities {
+        writeln!(
+            out,
+            "            {} => Ok(Self::{}),",
+            entity.id, entity.name
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "            _ => Err(Error::UnknownId {{ kind: \"{kind}\", id }}),"
+    )
+    .unwrap();
+    writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+
+    writeln!(out, "impl_id!({name});\n").unwrap();
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={ENTITIES}");
+
+    let path = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join(ENTITIES);
+    let data: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let (units, abilities, upgrades) = (
+        entities(&data, "Unit"),
+        entities(&data, "Ability"),
+        entities(&data, "Upgrade"),
+    );
+
+    // races of the units an ability is available to
+    let mut ability_races: BTreeMap<u64, BTreeSet<&str>> = BTreeMap::new();
+    for unit in &units {
+        let Some(race) = unit["race"].as_str() else {
+            continue;
+        };
+        for ability in unit["abilities"].as_array().into_iter().flatten() {
+            if let Some(id) = ability["ability"].as_u64() {
+                ability_races.entry(id).or_default().insert(race);
+            }
+        }
+    }
+    // races of the abilities researching an upgrade
+    let mut upgrade_races: BTreeMap<u64, BTreeSet<&str>> = BTreeMap::new();
+    for ability in &abilities {
+        let Some(upgrade) = ability["target"]["Research"]["upgrade"].as_u64() else {
+            continue;
+        };
+        let races = ability["id"]
+            .as_u64()
+            .and_then(|id| ability_races.get(&id))
+            .into_iter()
+            .flatten()
+            .copied();
+        upgrade_races.entry(upgrade).or_default().extend(races);
+    }
+
+    let mut out = String::from("// generated by build.rs from entities.json\n\n");
+    write_enum(
+        &mut out,
+        "UnitTypeId",
+        "unit type",
+        &to_entities(&units, |unit, _| unit["race"].as_str()),
+        true,
+    );
+    write_enum(
+        &mut out,
+        "AbilityId",
+        "ability",
+        &to_entities(&abilities, |_, id| single_race(ability_races.get(&id))),
+        false,
+    );
+    write_enum(
+        &mut out,
+        "UpgradeId",
+        "upgrade",
+        &to_entities(&upgrades, |_, id| single_race(upgrade_races.get(&id))),
+        false,
+    );
+
+    let dest = Path::new(&std::env::var("OUT_DIR").unwrap()).join("ids.rs");
+    std::fs::write(dest, out).unwrap();
+}
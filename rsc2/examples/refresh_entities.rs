@@ -0,0 +1,251 @@
+//! Refreshes `entities.json` from the data of a running game.
+//!
+//! usage: refresh_entities --map <map> [--addr 127.0.0.1:8000] [--entities <path>] [--write]
+//!
+//! A game is created against the computer to request the data, records of the existing file
+//! are then updated with the values reported by the api. Fields the api doesn't report (health,
+//! shields, energy, footprints, unit abilities, ...) and the ability names are kept from the
+//! existing file, records only reported by the api are added as is. Without `--write` only the
+//! differences are printed.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use rsc2::{
+    GameData,
+    data::{AbilityData, AbilityTarget, Entities, UnitData, UpgradeData},
+    prelude::{Core, Difficulty, Player, Race, create_game},
+    protocol,
+};
+use serde::Serialize;
+use serde_json::{Value, ser::PrettyFormatter};
+
+/// Refreshes `entities.json` from the data of a running game
+#[derive(Debug, Parser)]
+struct Args {
+    /// Address of the game api
+    #[arg(long, default_value = "127.0.0.1:8000")]
+    addr: String,
+    /// Local map path or Battle.net map name
+    #[arg(long)]
+    map: String,
+    #[arg(long, default_value_os_t = bundled_entities())]
+    entities: PathBuf,
+    /// Write the refreshed records, only the differences are printed otherwise
+    #[arg(long)]
+    write: bool,
+}
+
+fn bundled_entities() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("entities.json")
+}
+
+async fn request_data(addr: &str, map: &str) -> rsc2::Result<protocol::ResponseData> {
+    let mut sm = Core::default();
+    let (state, mut connection) = create_game(
+        &mut sm,
+        addr,
+        [
+            Player::participant("datagen", Race::Terran),
+            Player::bot("opponent", Race::Zerg, Difficulty::VeryEasy),
+        ],
+        map,
+        false,
+    )
+    .await?;
+    let mut listener = state.stream(&mut connection);
+    let data = listener
+        .call(protocol::RequestData {
+            ability_id: Some(true),
+            unit_type_id: Some(true),
+            upgrade_id: Some(true),
+            buff_id: Some(true),
+            effect_id: Some(true),
+        })
+        .await?;
+    data.ok_or(rsc2::Error::Closed)
+}
+
+fn refresh_unit(old: &UnitData, new: &UnitData) -> UnitData {
+    let mut unit = new.clone();
+    unit.max_health = old.max_health;
+    unit.max_shield = old.max_shield;
+    unit.max_energy = old.max_energy;
+    unit.start_energy = old.start_energy;
+    unit.speed_creep_mul = old.speed_creep_mul;
+    unit.size = old.size;
+    unit.radius = old.radius;
+    unit.accepts_addon = old.accepts_addon;
+    unit.needs_power = old.needs_power;
+    unit.needs_creep = old.needs_creep;
+    unit.needs_gayser = old.needs_gayser;
+    unit.is_addon = old.is_addon;
+    unit.abilities.clone_from(&old.abilities);
+    unit.normal_mode = old.normal_mode;
+    unit.cargo_capacity = old.cargo_capacity;
+    unit.detection_range = old.detection_range;
+    unit.power_radius = old.power_radius;
+    for (weapon, old) in unit.weapons.iter_mut().zip(&old.weapons) {
+        weapon.damage_splash = old.damage_splash;
+    }
+    unit
+}
+
+fn refresh_ability(old: &AbilityData, new: &AbilityData) -> AbilityData {
+    let mut ability = new.clone();
+    ability.name.clone_from(&old.name);
+    ability.energy_cost = old.energy_cost;
+    ability.cooldown = old.cooldown;
+    ability.effect.clone_from(&old.effect);
+    ability.buff.clone_from(&old.buff);
+    // the api only tells trains, builds and researches apart
+    let generic = matches!(
+        new.target,
+        AbilityTarget::None
+            | AbilityTarget::Point
+            | AbilityTarget::Unit
+            | AbilityTarget::PointOrUnit
+    );
+    let same_product =
+        new.target.produces().is_some() && old.target.produces() == new.target.produces();
+    if generic || same_product {
+        ability.target = old.target.clone();
+    }
+    if ability.cost.is_zero() {
+        ability.cost = old.cost;
+    }
+    ability
+}
+
+fn refresh_upgrade(_: &UpgradeData, new: &UpgradeData) -> UpgradeData {
+    new.clone()
+}
+
+/// Refreshes the records of `old` found in `new`, records missing from `new` are kept and
+/// records only found in `new` are added. Records are sorted by id.
+fn refresh<T: Clone>(
+    old: &[T],
+    new: &[T],
+    id: impl Fn(&T) -> u32,
+    merge: impl Fn(&T, &T) -> T,
+) -> Vec<T> {
+    let mut records: BTreeMap<u32, T> = new
+        .iter()
+        .map(|record| (id(record), record.clone()))
+        .collect();
+    for record in old {
+        let refreshed = match records.get(&id(record)) {
+            Some(live) => merge(record, live),
+            None => record.clone(),
+        };
+        records.insert(id(record), refreshed);
+    }
+    records.into_values().collect()
+}
+
+/// Prints the fields that changed between two versions of a record list and the records added,
+/// returns the number of changes
+fn report<T: Serialize>(kind: &str, old: &[T], new: &[T], live: &[T]) -> usize {
+    let index = |records: &[T]| -> BTreeMap<u64, Value> {
+        records
+            .iter()
+            .map(|record| serde_json::to_value(record).expect("records serialize"))
+            .map(|value| (value["id"].as_u64().unwrap_or_default(), value))
+            .collect()
+    };
+    let (old, new, live) = (index(old), index(new), index(live));
+
+    let mut changes = 0;
+    let mut added = 0;
+    for (id, after) in &new {
+        let Some(before) = old.get(id) else {
+            println!("{kind} {} ({id}): added", after["name"]);
+            added += 1;
+            continue;
+        };
+        let (Value::Object(before), Value::Object(after)) = (before, after) else {
+            continue;
+        };
+        for (field, value) in after {
+            let previous = before.get(field).unwrap_or(&Value::Null);
+            if previous != value {
+                println!(
+                    "{kind} {} ({id}): {field} {previous} -> {value}",
+                    after["name"]
+                );
+                changes += 1;
+            }
+        }
+    }
+    let missing = old.keys().filter(|id| !live.contains_key(id)).count();
+    println!(
+        "{kind}: {changes} changed fields, {added} records added, {missing} records not \
+         reported by the game"
+    );
+    changes + added
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> rsc2::Result<()> {
+    pretty_env_logger::init_timed();
+    let args = Args::parse();
+
+    let existing = GameData::load(&args.entities)?.to_entities();
+    let live = GameData::from(&request_data(&args.addr, &args.map).await?).to_entities();
+
+    let refreshed = Entities {
+        ability: refresh(&existing.ability, &live.ability, |a| a.id, refresh_ability),
+        unit: refresh(&existing.unit, &live.unit, |u| u.id, refresh_unit),
+        upgrade: refresh(&existing.upgrade, &live.upgrade, |u| u.id, refresh_upgrade),
+    };
+
+    let (old, new) = (&existing, &refreshed);
+    let mut changes = report("Ability", &old.ability, &new.ability, &live.ability);
+    changes += report("Unit", &old.unit, &new.unit, &live.unit);
+    changes += report("Upgrade", &old.upgrade, &new.upgrade, &live.upgrade);
+
+    if args.write && changes > 0 {
+        let mut json = Vec::new();
+        let mut serializer = serde_json::Serializer::with_formatter(
+            &mut json,
+            PrettyFormatter::with_indent(b"    "),
+        );
+        refreshed.serialize(&mut serializer)?;
+        json.push(b'\n');
+        std::fs::write(&args.entities, json)?;
+        println!("wrote {}", args.entities.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rsc2::data::Cost;
+
+    use super::*;
+
+    fn upgrade(id: u32, minerals: u32) -> UpgradeData {
+        UpgradeData {
+            id,
+            name: format!("Upgrade{id}"),
+            cost: Cost {
+                minerals,
+                gas: 0,
+                time: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_refresh() {
+        let old = [upgrade(3, 100), upgrade(1, 100)];
+        let live = [upgrade(2, 50), upgrade(3, 150)];
+        let new = refresh(&old, &live, |u| u.id, refresh_upgrade);
+        assert_eq!(new, [upgrade(1, 100), upgrade(2, 50), upgrade(3, 150)]);
+
+        // one changed field and one added record
+        assert_eq!(report("Upgrade", &old, &new, &live), 2);
+        assert_eq!(report("Upgrade", &new, &new, &live), 0);
+    }
+}
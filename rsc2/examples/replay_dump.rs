@@ -0,0 +1,52 @@
+use log::info;
+use rsc2::{
+    prelude::{Core, start_replay},
+    protocol,
+};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> rsc2::Result<()> {
+    pretty_env_logger::init_timed();
+
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .expect("usage: replay_dump <replay> [player id]");
+    let player_id = args.next().and_then(|id| id.parse().ok()).unwrap_or(1);
+
+    let mut sm = Core::default();
+    let (state, mut connection) = start_replay(&mut sm, "127.0.0.1:8000", &path, player_id).await?;
+    let mut observer = state.observe(&mut connection, 22);
+
+    let replay_info = observer
+        .replay_info(protocol::RequestReplayInfo {
+            replay: Some(protocol::request_replay_info::Replay::ReplayPath(
+                path.clone(),
+            )),
+            download_data: Some(false),
+        })
+        .await?;
+    info!(
+        "{} on {}, {} loops",
+        path,
+        replay_info.map_name(),
+        replay_info.game_duration_loops()
+    );
+
+    observer.player_perspective(player_id).await?;
+
+    while let Some(observation) = observer.next_observation().await? {
+        let units = observation
+            .observation
+            .as_ref()
+            .and_then(|obs| obs.raw_data.as_ref())
+            .map_or(0, |raw| raw.units.len());
+        info!("game loop {}: {} units", observer.game_loop(), units);
+        if !observation.player_result.is_empty() {
+            info!("results: {:?}", observation.player_result);
+        }
+    }
+
+    let _ended = observer.into_ended();
+    Ok(())
+}
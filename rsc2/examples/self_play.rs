@@ -0,0 +1,70 @@
+use log::info;
+use rsc2::{
+    multiplayer::{self, GamePorts, Participant},
+    prelude::{Actions, Agent, LaunchOptions, Player, Race, StepMode},
+    protocol,
+};
+
+/// Counts the steps it was given and never acts
+#[derive(Default)]
+struct Idle {
+    name: &'static str,
+    steps: usize,
+}
+
+impl Agent for Idle {
+    async fn on_step(&mut self, _: &protocol::ResponseObservation) -> Actions {
+        self.steps += 1;
+        Actions::new()
+    }
+
+    async fn on_end(&mut self, results: &[protocol::PlayerResult]) {
+        info!(
+            "{} ended after {} steps: {:?}",
+            self.name, self.steps, results
+        );
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> rsc2::Result<()> {
+    pretty_env_logger::init_timed();
+
+    let binary = std::env::var("SC2_BINARY").unwrap_or_else(|_| {
+        r"C:\Program Files (x86)\StarCraft II\Versions\Base75689\SC2_x64.exe".into()
+    });
+    let (mut host, mut guest) = futures::try_join!(
+        LaunchOptions::new(&binary).port(8167).launch(),
+        LaunchOptions::new(&binary).port(8168).launch(),
+    )?;
+
+    let (mut first, mut second) = (
+        Idle {
+            name: "first",
+            ..Default::default()
+        },
+        Idle {
+            name: "second",
+            ..Default::default()
+        },
+    );
+
+    multiplayer::run_game(
+        Participant::from_instance(
+            &mut host,
+            Player::participant("first", Race::Terran),
+            &mut first,
+        ),
+        Participant::from_instance(
+            &mut guest,
+            Player::participant("second", Race::Zerg),
+            &mut second,
+        ),
+        r"C:\Program Files (x86)\StarCraft II\Maps\EphemeronLE.SC2Map",
+        StepMode::Step(8),
+        &GamePorts::unused(1)?,
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,416 @@
+//! Unit commands and the per step batch sending them.
+//!
+//! Commands are built from [`Unit`] and [`Units`](crate::Units), `units.attack(point)` or
+//! `unit.train(UnitTypeId::SCV)`, and pushed into [`Actions`]. The batch is sent as a single
+//! `RequestAction` where identical commands given to several units are merged into one. The
+//! results of the request are handed back on the next step as [`ActionResults`].
+use rsc2_pb::protocol::{self, action_raw, action_raw_unit_command};
+
+use crate::{AbilityId, Unit, UnitTypeId, Units, geometry::Point2};
+
+/// Target of a unit command
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Target {
+    #[default]
+    None,
+    Point(Point2),
+    /// Tag of the targeted unit
+    Unit(u64),
+}
+
+impl From<Point2> for Target {
+    fn from(point: Point2) -> Self {
+        Self::Point(point)
+    }
+}
+
+impl From<(f32, f32)> for Target {
+    fn from(point: (f32, f32)) -> Self {
+        Self::Point(point.into())
+    }
+}
+
+impl From<protocol::Point2D> for Target {
+    fn from(point: protocol::Point2D) -> Self {
+        Self::Point(point.into())
+    }
+}
+
+impl From<Unit<'_>> for Target {
+    fn from(unit: Unit<'_>) -> Self {
+        Self::Unit(unit.tag())
+    }
+}
+
+impl From<&Unit<'_>> for Target {
+    fn from(unit: &Unit<'_>) -> Self {
+        Self::Unit(unit.tag())
+    }
+}
+
+impl From<Target> for Option<action_raw_unit_command::Target> {
+    fn from(target: Target) -> Self {
+        match target {
+            Target::None => None,
+            Target::Point(point) => Some(action_raw_unit_command::Target::TargetWorldSpacePos(
+                point.into(),
+            )),
+            Target::Unit(tag) => Some(action_raw_unit_command::Target::TargetUnitTag(tag)),
+        }
+    }
+}
+
+/// An ability used by one or more units
+#[derive(Debug, Clone, PartialEq)]
+pub struct Command {
+    ability: u32,
+    units: Vec<u64>,
+    target: Target,
+    queued: bool,
+}
+
+impl Command {
+    pub fn new(
+        ability: impl Into<u32>,
+        units: impl IntoIterator<Item = u64>,
+        target: impl Into<Target>,
+    ) -> Self {
+        Self {
+            ability: ability.into(),
+            units: units.into_iter().collect(),
+            target: target.into(),
+            queued: false,
+        }
+    }
+    /// Appends the command to the orders of the units instead of replacing them
+    pub fn queued(mut self) -> Self {
+        self.queued = true;
+        self
+    }
+
+    pub fn ability(&self) -> u32 {
+        self.ability
+    }
+    pub fn units(&self) -> &[u64] {
+        &self.units
+    }
+    pub fn target(&self) -> Target {
+        self.target
+    }
+    pub fn is_queued(&self) -> bool {
+        self.queued
+    }
+
+    /// Same ability, target and queueing, the units may differ
+    fn same_order(&self, other: &Self) -> bool {
+        self.ability == other.ability && self.target == other.target && self.queued == other.queued
+    }
+    fn shares_units(&self, other: &Self) -> bool {
+        self.units.iter().any(|tag| other.units.contains(tag))
+    }
+}
+
+impl From<Command> for protocol::Action {
+    fn from(command: Command) -> Self {
+        let command = protocol::ActionRawUnitCommand {
+            ability_id: Some(command.ability as i32),
+            unit_tags: command.units,
+            queue_command: Some(command.queued),
+            target: command.target.into(),
+        };
+        protocol::Action {
+            action_raw: Some(protocol::ActionRaw {
+                action: Some(action_raw::Action::UnitCommand(command)),
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// Actions of a step, sent together in one `RequestAction`
+#[derive(Debug, Clone, Default)]
+pub struct Actions {
+    commands: Vec<Command>,
+    raw: Vec<protocol::Action>,
+}
+
+impl Actions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `command`, merging it into an earlier identical order when no command in between
+    /// involves the same units so the order of each unit's commands is kept
+    pub fn push(&mut self, command: Command) {
+        if command.units.is_empty() {
+            return;
+        }
+        let merge = self
+            .commands
+            .iter()
+            .rposition(|other| other.same_order(&command))
+            .filter(|&i| {
+                !self.commands[i + 1..]
+                    .iter()
+                    .any(|other| other.shares_units(&command))
+            });
+        match merge {
+            Some(i) => {
+                let units = &mut self.commands[i].units;
+                for tag in command.units {
+                    if !units.contains(&tag) {
+                        units.push(tag);
+                    }
+                }
+            }
+            None => self.commands.push(command),
+        }
+    }
+    /// Adds an action sent as is, such as chat or camera actions
+    pub fn push_raw(&mut self, action: protocol::Action) {
+        self.raw.push(action);
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+    pub fn len(&self) -> usize {
+        self.commands.len() + self.raw.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty() && self.raw.is_empty()
+    }
+
+    /// Actions of the `RequestAction`, unit commands first
+    pub fn into_actions(self) -> Vec<protocol::Action> {
+        self.into_request().1
+    }
+    /// Commands along with the actions sending them, to correlate the results of the request
+    pub(crate) fn into_request(self) -> (Vec<Command>, Vec<protocol::Action>) {
+        let actions = self
+            .commands
+            .iter()
+            .cloned()
+            .map(protocol::Action::from)
+            .chain(self.raw)
+            .collect();
+        (self.commands, actions)
+    }
+}
+
+impl Extend<Command> for Actions {
+    fn extend<I: IntoIterator<Item = Command>>(&mut self, iter: I) {
+        for command in iter {
+            self.push(command);
+        }
+    }
+}
+
+impl FromIterator<Command> for Actions {
+    fn from_iter<I: IntoIterator<Item = Command>>(iter: I) -> Self {
+        let mut actions = Self::new();
+        actions.extend(iter);
+        actions
+    }
+}
+
+impl From<Vec<protocol::Action>> for Actions {
+    fn from(raw: Vec<protocol::Action>) -> Self {
+        Self {
+            commands: Vec::new(),
+            raw,
+        }
+    }
+}
+
+/// Result of a command sent on the previous step
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandResult {
+    pub command: Command,
+    pub result: protocol::ActionResult,
+}
+
+impl CommandResult {
+    pub fn is_success(&self) -> bool {
+        self.result == protocol::ActionResult::Success
+    }
+}
+
+/// Results of the actions sent on a step, in the order they were pushed
+#[derive(Debug, Clone, Default)]
+pub struct ActionResults {
+    commands: Vec<CommandResult>,
+    raw: Vec<protocol::ActionResult>,
+}
+
+impl ActionResults {
+    /// Pairs `commands` with the results of the `RequestAction` they were sent with, results
+    /// past the commands belong to the raw actions
+    pub(crate) fn new(commands: Vec<Command>, response: &protocol::ResponseAction) -> Self {
+        let mut results = response.result.iter().map(|&result| {
+            protocol::ActionResult::try_from(result).unwrap_or(protocol::ActionResult::Error)
+        });
+        Self {
+            commands: commands
+                .into_iter()
+                .zip(results.by_ref())
+                .map(|(command, result)| CommandResult { command, result })
+                .collect(),
+            raw: results.collect(),
+        }
+    }
+
+    pub fn commands(&self) -> &[CommandResult] {
+        &self.commands
+    }
+    /// Results of the actions added with [`Actions::push_raw`]
+    pub fn raw(&self) -> &[protocol::ActionResult] {
+        &self.raw
+    }
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty() && self.raw.is_empty()
+    }
+    /// Whether the game accepted every command and raw action
+    pub fn is_success(&self) -> bool {
+        self.failed().next().is_none()
+            && self
+                .raw
+                .iter()
+                .all(|&result| result == protocol::ActionResult::Success)
+    }
+    /// Commands the game refused
+    pub fn failed(&self) -> impl Iterator<Item = &CommandResult> {
+        self.commands.iter().filter(|result| !result.is_success())
+    }
+    /// Results of the commands given to the unit `tag`
+    pub fn of_unit(&self, tag: u64) -> impl Iterator<Item = &CommandResult> {
+        self.commands
+            .iter()
+            .filter(move |result| result.command.units.contains(&tag))
+    }
+}
+
+impl Unit<'_> {
+    pub fn command(&self, ability: impl Into<u32>, target: impl Into<Target>) -> Command {
+        Command::new(ability, [self.tag()], target)
+    }
+    pub fn attack(&self, target: impl Into<Target>) -> Command {
+        self.command(AbilityId::ATTACK, target)
+    }
+    pub fn move_to(&self, target: impl Into<Target>) -> Command {
+        self.command(AbilityId::MOVE, target)
+    }
+    /// Right click on `target`
+    pub fn smart(&self, target: impl Into<Target>) -> Command {
+        self.command(AbilityId::SMART, target)
+    }
+    pub fn gather(&self, target: impl Into<Target>) -> Command {
+        self.command(AbilityId::HARVEST_GATHER, target)
+    }
+    pub fn stop(&self) -> Command {
+        self.command(AbilityId::STOP, Target::None)
+    }
+    pub fn hold_position(&self) -> Command {
+        self.command(AbilityId::HOLDPOSITION, Target::None)
+    }
+
+    /// Trains or morphs into `unit_type`, `None` if the game data has no ability producing it
+    pub fn train(&self, unit_type: UnitTypeId) -> Option<Command> {
+        let ability = self.data().producer(unit_type)?;
+        Some(self.command(ability.id, Target::None))
+    }
+    /// Builds `unit_type` at `position`, `None` if the game data has no ability producing it
+    pub fn build(&self, unit_type: UnitTypeId, position: impl Into<Point2>) -> Option<Command> {
+        let ability = self.data().producer(unit_type)?;
+        Some(self.command(ability.id, position.into()))
+    }
+    /// Builds `unit_type` on the unit `on`, such as a refinery on a vespene geyser
+    pub fn build_on(&self, unit_type: UnitTypeId, on: &Unit<'_>) -> Option<Command> {
+        let ability = self.data().producer(unit_type)?;
+        Some(self.command(ability.id, on))
+    }
+}
+
+impl Units<'_> {
+    /// One command given to every unit of the selection
+    pub fn command(&self, ability: impl Into<u32>, target: impl Into<Target>) -> Command {
+        Command::new(ability, self.tags(), target)
+    }
+    pub fn attack(&self, target: impl Into<Target>) -> Command {
+        self.command(AbilityId::ATTACK, target)
+    }
+    pub fn move_to(&self, target: impl Into<Target>) -> Command {
+        self.command(AbilityId::MOVE, target)
+    }
+    pub fn smart(&self, target: impl Into<Target>) -> Command {
+        self.command(AbilityId::SMART, target)
+    }
+    pub fn gather(&self, target: impl Into<Target>) -> Command {
+        self.command(AbilityId::HARVEST_GATHER, target)
+    }
+    pub fn stop(&self) -> Command {
+        self.command(AbilityId::STOP, Target::None)
+    }
+    pub fn hold_position(&self) -> Command {
+        self.command(AbilityId::HOLDPOSITION, Target::None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut actions = Actions::new();
+        actions.push(Command::new(AbilityId::ATTACK, [1], (1.0, 1.0)));
+        actions.push(Command::new(AbilityId::ATTACK, [2, 1], (1.0, 1.0)));
+        actions.push(Command::new(AbilityId::MOVE, [3], (1.0, 1.0)));
+        actions.push(Command::new(AbilityId::ATTACK, [4], (1.0, 1.0)).queued());
+        // unit 3 moves first, its attack can't be merged before the move
+        actions.push(Command::new(AbilityId::ATTACK, [3], (1.0, 1.0)));
+        actions.push(Command::new(AbilityId::STOP, Vec::new(), Target::None));
+
+        let units: Vec<_> = actions.commands().iter().map(Command::units).collect();
+        assert_eq!(units, [&[1, 2][..], &[3], &[4], &[3]]);
+
+        let actions = actions.into_actions();
+        assert_eq!(actions.len(), 4);
+        let Some(action_raw::Action::UnitCommand(command)) = actions[0]
+            .action_raw
+            .as_ref()
+            .and_then(|raw| raw.action.as_ref())
+        else {
+            panic!("expected a unit command");
+        };
+        assert_eq!(command.ability_id, Some(AbilityId::ATTACK.into()));
+        assert_eq!(command.unit_tags, [1, 2]);
+        assert_eq!(command.queue_command, Some(false));
+    }
+
+    #[test]
+    fn test_results() {
+        let mut actions = Actions::new();
+        actions.push(Command::new(AbilityId::ATTACK, [1], (1.0, 1.0)));
+        actions.push(Command::new(AbilityId::MOVE, [2], (1.0, 1.0)));
+        actions.push_raw(protocol::Action::default());
+
+        let (commands, sent) = actions.into_request();
+        assert_eq!(sent.len(), 3);
+        let response = protocol::ResponseAction {
+            result: vec![
+                protocol::ActionResult::Success as i32,
+                protocol::ActionResult::NotEnoughMinerals as i32,
+                protocol::ActionResult::Error as i32,
+            ],
+        };
+        let results = ActionResults::new(commands, &response);
+
+        let failed: Vec<_> = results.failed().collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].command.units(), [2]);
+        assert_eq!(failed[0].result, protocol::ActionResult::NotEnoughMinerals);
+        assert!(results.of_unit(1).all(CommandResult::is_success));
+        assert_eq!(results.raw(), [protocol::ActionResult::Error]);
+    }
+}
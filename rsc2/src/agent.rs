@@ -0,0 +1,185 @@
+//! Bot logic hooks and the runner driving them through a whole game.
+use std::future::Future;
+use std::net::ToSocketAddrs;
+
+use rsc2_pb::protocol;
+
+use crate::{
+    Connection, Core, InGame, Result, StepMode, Stepper,
+    action::{ActionResults, Actions},
+    create_game,
+    definitions::ToMapRef,
+};
+
+/// Game logic called by [`run_game`] at each stage of the game
+pub trait Agent {
+    /// Called once the game is joined, before the first step
+    fn on_start(
+        &mut self,
+        game_info: &protocol::ResponseGameInfo,
+        data: &protocol::ResponseData,
+    ) -> impl Future<Output = ()> {
+        let _ = (game_info, data);
+        async {}
+    }
+
+    /// Called with every observation, the returned actions are sent in a single request before
+    /// the next step
+    fn on_step(
+        &mut self,
+        observation: &protocol::ResponseObservation,
+    ) -> impl Future<Output = Actions>;
+
+    /// Called before [`on_step`](Self::on_step) with the results of the actions sent on the
+    /// previous step, commands refused by the game such as `NotEnoughMinerals` show up here
+    fn on_action_results(&mut self, results: &ActionResults) -> impl Future<Output = ()> {
+        let _ = results;
+        async {}
+    }
+
+    /// Called once the game has ended with the results of every player
+    fn on_end(&mut self, results: &[protocol::PlayerResult]) -> impl Future<Output = ()> {
+        let _ = results;
+        async {}
+    }
+}
+
+/// Creates a game on a launched `core`, joins it and plays `agent` until the game ends.
+///
+/// The game is realtime if `mode` is [`StepMode::Realtime`], `core` is left in the `Ended`
+/// state and the results of the game are returned.
+pub async fn run_game<P: Into<protocol::PlayerSetup>, A: Agent>(
+    core: &mut Core,
+    addr: impl ToSocketAddrs,
+    players: impl IntoIterator<Item = P>,
+    map: impl ToMapRef,
+    mode: StepMode,
+    agent: &mut A,
+) -> Result<Vec<protocol::PlayerResult>> {
+    let (state, mut connection) = create_game(core, addr, players, map, mode.is_realtime()).await?;
+    play(state, &mut connection, mode, agent).await
+}
+
+/// Plays `agent` in an already joined game until it ends
+pub async fn play<A: Agent>(
+    state: InGame<'_>,
+    connection: &mut Connection,
+    mode: StepMode,
+    agent: &mut A,
+) -> Result<Vec<protocol::PlayerResult>> {
+    let mut stepper = Stepper::new(state.stream(connection), mode);
+
+    let listener = stepper.listener();
+    let game_info = listener.call(protocol::RequestGameInfo {}).await?;
+    let data = listener
+        .call(protocol::RequestData {
+            ability_id: Some(true),
+            unit_type_id: Some(true),
+            upgrade_id: Some(true),
+            buff_id: Some(true),
+            effect_id: Some(true),
+        })
+        .await?;
+
+    if let (Some(game_info), Some(data)) = (game_info, data) {
+        agent.on_start(&game_info, &data).await;
+        while stepper
+            .step(async |observation, results| {
+                if !results.is_empty() {
+                    agent.on_action_results(results).await;
+                }
+                agent.on_step(observation).await
+            })
+            .await?
+        {}
+    }
+
+    let ended = stepper.into_ended();
+    let results = ended.results().to_vec();
+    agent.on_end(&results).await;
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        action::{Command, Target},
+        connect_s2api,
+        testing::{fake_api, fake_game, fake_step_game},
+    };
+
+    /// Attacks with unit `1` on every step and records the callbacks
+    #[derive(Default)]
+    struct Recorder {
+        started: bool,
+        steps: Vec<u32>,
+        succeeded: usize,
+        results: Option<Vec<protocol::PlayerResult>>,
+    }
+
+    impl Agent for Recorder {
+        async fn on_start(&mut self, _: &protocol::ResponseGameInfo, _: &protocol::ResponseData) {
+            self.started = true;
+        }
+        async fn on_step(&mut self, observation: &protocol::ResponseObservation) -> Actions {
+            let game_loop = observation.observation.as_ref().map(|o| o.game_loop());
+            self.steps.push(game_loop.unwrap_or_default());
+            Actions::from_iter([Command::new(23u32, [1], Target::None)])
+        }
+        async fn on_action_results(&mut self, results: &ActionResults) {
+            self.succeeded += results.commands().iter().filter(|r| r.is_success()).count();
+        }
+        async fn on_end(&mut self, results: &[protocol::PlayerResult]) {
+            self.results = Some(results.to_vec());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_play() {
+        let (addr, _server) = fake_api(fake_game(3)).await;
+        let mut connection = connect_s2api(addr).await.unwrap();
+        let mut core = Core::init();
+        let state = core.launched().unwrap();
+        let state = state
+            .join_game(&mut connection, Default::default())
+            .await
+            .unwrap();
+
+        let mut agent = Recorder::default();
+        let results = play(state, &mut connection, StepMode::Step(1), &mut agent)
+            .await
+            .unwrap();
+        assert!(agent.started);
+        assert_eq!(agent.steps, [0, 1, 2]);
+        // the game ends before the results of the actions of the last step are reported
+        assert_eq!(agent.succeeded, 2);
+        assert_eq!(results.len(), 1);
+        assert_eq!(agent.results, Some(results));
+        assert_eq!(core.player_results().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_play_ended_on_step() {
+        let (addr, _server) = fake_api(fake_step_game(2)).await;
+        let mut connection = connect_s2api(addr).await.unwrap();
+        let mut core = Core::init();
+        let state = core.launched().unwrap();
+        let state = state
+            .join_game(&mut connection, Default::default())
+            .await
+            .unwrap();
+
+        let mut agent = Recorder::default();
+        let results = play(state, &mut connection, StepMode::Step(1), &mut agent)
+            .await
+            .unwrap();
+        assert_eq!(agent.steps, [0, 1]);
+        let victory = protocol::PlayerResult {
+            player_id: Some(1),
+            result: Some(protocol::Result::Victory as i32),
+        };
+        assert_eq!(agent.results, Some(vec![victory]));
+        assert_eq!(agent.results, Some(results));
+    }
+}
@@ -0,0 +1,254 @@
+//! Typed api calls on top of a [`Multiplexer`](crate::mux::Multiplexer).
+use rsc2_pb::protocol::{self, Status, response};
+
+use crate::{
+    Error, Result,
+    action::{ActionResults, Actions},
+    mux::{self, Driver, Multiplexer},
+};
+
+/// A request message along with the message it is answered by
+pub trait ApiRequest: Into<protocol::Request> {
+    type Response;
+    /// Name of the response variant, used in errors
+    const RESPONSE: &'static str;
+    fn from_response(response: response::Response) -> Option<Self::Response>;
+}
+
+macro_rules! impl_api_request {
+    {$($variant:ident => $request:ident => $response:ident),+} => {
+        $(
+        impl ApiRequest for protocol::$request {
+            type Response = protocol::$response;
+            const RESPONSE: &'static str = stringify!($variant);
+            fn from_response(response: response::Response) -> Option<Self::Response> {
+                match response {
+                    response::Response::$variant(response) => Some(response),
+                    _ => None,
+                }
+            }
+        }
+        )*
+    };
+}
+
+impl_api_request! {
+    CreateGame => RequestCreateGame => ResponseCreateGame,
+    JoinGame => RequestJoinGame => ResponseJoinGame,
+    RestartGame => RequestRestartGame => ResponseRestartGame,
+    StartReplay => RequestStartReplay => ResponseStartReplay,
+    LeaveGame => RequestLeaveGame => ResponseLeaveGame,
+    QuickSave => RequestQuickSave => ResponseQuickSave,
+    QuickLoad => RequestQuickLoad => ResponseQuickLoad,
+    Quit => RequestQuit => ResponseQuit,
+    GameInfo => RequestGameInfo => ResponseGameInfo,
+    Observation => RequestObservation => ResponseObservation,
+    Action => RequestAction => ResponseAction,
+    ObsAction => RequestObserverAction => ResponseObserverAction,
+    Step => RequestStep => ResponseStep,
+    Data => RequestData => ResponseData,
+    Query => RequestQuery => ResponseQuery,
+    SaveReplay => RequestSaveReplay => ResponseSaveReplay,
+    MapCommand => RequestMapCommand => ResponseMapCommand,
+    ReplayInfo => RequestReplayInfo => ResponseReplayInfo,
+    AvailableMaps => RequestAvailableMaps => ResponseAvailableMaps,
+    SaveMap => RequestSaveMap => ResponseSaveMap,
+    Ping => RequestPing => ResponsePing,
+    Debug => RequestDebug => ResponseDebug
+}
+
+/// Checks the response envelope and extracts the message answering `R` along with the
+/// response id.
+pub(crate) fn typed_response<R: ApiRequest>(
+    response: protocol::Response,
+) -> Result<(u32, R::Response)> {
+    let status = response.status();
+    let protocol::Response {
+        id,
+        response,
+        error,
+        ..
+    } = response;
+    let id = id.unwrap_or_default();
+
+    if !error.is_empty() {
+        return Err(Error::Response { id, errors: error });
+    }
+    if matches!(status, Status::Quit | Status::Unknown) {
+        info!("status: {:?}, interupting state machine", status);
+        return Err(Error::Status { id, status });
+    }
+    response
+        .and_then(R::from_response)
+        .map(|response| (id, response))
+        .ok_or(Error::Unexpected {
+            id,
+            expected: R::RESPONSE,
+        })
+}
+
+/// Cloneable api client, calls can be issued concurrently.
+#[derive(Debug, Clone)]
+pub struct Client {
+    mux: Multiplexer,
+}
+
+impl From<Multiplexer> for Client {
+    fn from(mux: Multiplexer) -> Self {
+        Self { mux }
+    }
+}
+
+impl Client {
+    /// Multiplexes `transport`, the returned driver must be polled for calls to complete.
+    pub fn new<T>(transport: T) -> (Self, Driver<T>) {
+        let (mux, driver) = mux::multiplex(transport);
+        (Self::from(mux), driver)
+    }
+
+    async fn send<R: ApiRequest>(&self, request: R) -> Result<(u32, R::Response)> {
+        typed_response::<R>(self.mux.call(request.into()).await?)
+    }
+
+    /// Sends any request and extracts its typed response
+    pub async fn call<R: ApiRequest>(&self, request: R) -> Result<R::Response> {
+        self.send(request).await.map(|(_, response)| response)
+    }
+
+    pub async fn observation(&self) -> Result<protocol::ResponseObservation> {
+        self.call(protocol::RequestObservation::default()).await
+    }
+    pub async fn game_info(&self) -> Result<protocol::ResponseGameInfo> {
+        self.call(protocol::RequestGameInfo {}).await
+    }
+    /// Advances the simulation by `count` game loops, only available in non realtime games
+    pub async fn step(&self, count: u32) -> Result<protocol::ResponseStep> {
+        self.call(protocol::RequestStep { count: Some(count) })
+            .await
+    }
+    /// Sends `actions`, commands the game refused are reported by the results rather than as
+    /// an error, see [`checked_actions`](Self::checked_actions) to fail on them
+    pub async fn actions(&self, actions: impl Into<Actions>) -> Result<ActionResults> {
+        let (commands, actions) = actions.into().into_request();
+        let response = self.call(protocol::RequestAction { actions }).await?;
+        Ok(ActionResults::new(commands, &response))
+    }
+    /// Sends `actions` that all have to succeed, fails with [`Error::Action`] if the game refused
+    /// any of them
+    pub async fn checked_actions(&self, actions: impl Into<Actions>) -> Result<ActionResults> {
+        let (commands, actions) = actions.into().into_request();
+        let (id, response) = self.send(protocol::RequestAction { actions }).await?;
+        let results = ActionResults::new(commands, &response);
+        if !results.is_success() {
+            return Err(Error::Action { id, results });
+        }
+        Ok(results)
+    }
+    pub async fn data(&self, request: protocol::RequestData) -> Result<protocol::ResponseData> {
+        self.call(request).await
+    }
+    pub async fn query(&self, request: protocol::RequestQuery) -> Result<protocol::ResponseQuery> {
+        self.call(request).await
+    }
+    pub async fn save_replay(&self) -> Result<protocol::ResponseSaveReplay> {
+        self.call(protocol::RequestSaveReplay {}).await
+    }
+    pub async fn ping(&self) -> Result<protocol::ResponsePing> {
+        self.call(protocol::RequestPing {}).await
+    }
+    pub async fn debug(
+        &self,
+        commands: Vec<protocol::DebugCommand>,
+    ) -> Result<protocol::ResponseDebug> {
+        self.call(protocol::RequestDebug { debug: commands }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use rsc2_pb::protocol::request;
+
+    use super::*;
+    use crate::{
+        action::{Command, Target},
+        testing::{loopback, respond},
+    };
+
+    #[tokio::test]
+    async fn test_action_results() {
+        let (transport, (mut requests, responses)) = loopback();
+        let (client, driver) = Client::new(transport);
+        let driver = tokio::spawn(driver);
+
+        let mut actions = Actions::new();
+        actions.push(Command::new(1u32, [1], Target::None));
+        actions.push(Command::new(2u32, [2], Target::None));
+        actions.push_raw(protocol::Action::default());
+        let server = async {
+            let request = requests.next().await.unwrap();
+            let Some(request::Request::Action(action)) = &request.request else {
+                panic!("expected an action request, got {request:?}");
+            };
+            assert_eq!(action.actions.len(), 3);
+            let result = vec![
+                protocol::ActionResult::Success as i32,
+                protocol::ActionResult::NotEnoughMinerals as i32,
+                protocol::ActionResult::Success as i32,
+            ];
+            let response = response::Response::Action(protocol::ResponseAction { result });
+            responses
+                .unbounded_send(respond(request.id(), response))
+                .unwrap();
+        };
+
+        let (results, ()) = futures::join!(client.actions(actions), server);
+        let results = results.unwrap();
+        let failed: Vec<_> = results.failed().collect();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].command.units(), [2]);
+        assert_eq!(failed[0].result, protocol::ActionResult::NotEnoughMinerals);
+        assert_eq!(results.raw(), [protocol::ActionResult::Success]);
+
+        drop(client);
+        assert!(driver.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_checked_actions() {
+        let (transport, (mut requests, responses)) = loopback();
+        let (client, driver) = Client::new(transport);
+        let driver = tokio::spawn(driver);
+
+        let server = async {
+            for result in [
+                protocol::ActionResult::Success,
+                protocol::ActionResult::NotEnoughMinerals,
+            ] {
+                let request = requests.next().await.unwrap();
+                let result = vec![result as i32];
+                let response = response::Response::Action(protocol::ResponseAction { result });
+                responses
+                    .unbounded_send(respond(request.id(), response))
+                    .unwrap();
+            }
+        };
+        let command = || Actions::from_iter([Command::new(1u32, [1], Target::None)]);
+        let calls = async {
+            let accepted = client.checked_actions(command()).await;
+            let refused = client.checked_actions(command()).await;
+            (accepted, refused)
+        };
+
+        let ((accepted, refused), ()) = futures::join!(calls, server);
+        assert!(accepted.unwrap().is_success());
+        let Err(Error::Action { results, .. }) = refused else {
+            panic!("expected refused actions, got {refused:?}");
+        };
+        let failed: Vec<_> = results.failed().map(|failed| failed.result).collect();
+        assert_eq!(failed, [protocol::ActionResult::NotEnoughMinerals]);
+
+        drop(client);
+        assert!(driver.await.unwrap().is_ok());
+    }
+}
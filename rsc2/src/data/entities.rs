@@ -0,0 +1,294 @@
+//! Records of `entities.json`, field names and layout follow the file.
+use rsc2_pb::protocol::{Attribute, Race, weapon::TargetType};
+use serde::{Deserialize, Serialize};
+
+/// Top level of `entities.json`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Entities {
+    pub ability: Vec<AbilityData>,
+    pub unit: Vec<UnitData>,
+    pub upgrade: Vec<UpgradeData>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cost {
+    pub minerals: u32,
+    pub gas: u32,
+    #[serde(serialize_with = "whole")]
+    pub time: f32,
+}
+
+impl Cost {
+    pub fn is_zero(&self) -> bool {
+        self.minerals == 0 && self.gas == 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnitData {
+    pub id: u32,
+    pub name: String,
+    #[serde(with = "proto_name")]
+    pub race: Race,
+    #[serde(serialize_with = "whole")]
+    pub supply: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cargo_size: Option<u32>,
+    #[serde(serialize_with = "whole")]
+    pub max_health: f32,
+    #[serde(serialize_with = "whole")]
+    pub armor: f32,
+    #[serde(serialize_with = "whole")]
+    pub sight: f32,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "whole_opt"
+    )]
+    pub speed: Option<f32>,
+    #[serde(serialize_with = "whole")]
+    pub speed_creep_mul: f32,
+    #[serde(with = "proto_names")]
+    pub attributes: Vec<Attribute>,
+    pub size: u32,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "whole_opt"
+    )]
+    pub radius: Option<f32>,
+    pub accepts_addon: bool,
+    pub needs_power: bool,
+    pub needs_creep: bool,
+    pub needs_gayser: bool,
+    pub is_structure: bool,
+    pub is_addon: bool,
+    pub is_worker: bool,
+    pub is_townhall: bool,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "whole_opt"
+    )]
+    pub max_shield: Option<f32>,
+    pub weapons: Vec<Weapon>,
+    pub abilities: Vec<UnitAbility>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "whole_opt"
+    )]
+    pub max_energy: Option<f32>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "whole_opt"
+    )]
+    pub start_energy: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normal_mode: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cargo_capacity: Option<u32>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "whole_opt"
+    )]
+    pub detection_range: Option<f32>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "whole_opt"
+    )]
+    pub power_radius: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Weapon {
+    #[serde(with = "proto_name")]
+    pub target_type: TargetType,
+    #[serde(serialize_with = "whole")]
+    pub damage_per_hit: f32,
+    #[serde(serialize_with = "whole")]
+    pub damage_splash: f32,
+    pub attacks: u32,
+    #[serde(serialize_with = "whole")]
+    pub range: f32,
+    #[serde(serialize_with = "whole")]
+    pub cooldown: f32,
+    pub bonuses: Vec<DamageBonus>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DamageBonus {
+    #[serde(with = "proto_name")]
+    pub against: Attribute,
+    #[serde(serialize_with = "whole")]
+    pub damage: f32,
+}
+
+/// An ability available to a unit once its requirements are met
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnitAbility {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requirements: Vec<Requirement>,
+    pub ability: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Requirement {
+    Building(u32),
+    Upgrade(u32),
+    AddonTo(u32),
+    Addon(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AbilityData {
+    pub id: u32,
+    pub name: String,
+    #[serde(serialize_with = "whole")]
+    pub cast_range: f32,
+    #[serde(serialize_with = "whole")]
+    pub energy_cost: f32,
+    pub allow_minimap: bool,
+    pub allow_autocast: bool,
+    pub effect: Vec<u32>,
+    pub buff: Vec<u32>,
+    #[serde(serialize_with = "whole")]
+    pub cooldown: f32,
+    pub target: AbilityTarget,
+    pub cost: Cost,
+}
+
+/// What an ability is cast on, or what it produces
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AbilityTarget {
+    None,
+    Point,
+    Unit,
+    PointOrUnit,
+    Research {
+        upgrade: u32,
+    },
+    Morph {
+        produces: u32,
+    },
+    MorphPlace {
+        produces: u32,
+    },
+    Build {
+        produces: u32,
+    },
+    BuildInstant {
+        produces: u32,
+    },
+    BuildOnUnit {
+        produces: u32,
+    },
+    Train {
+        produces: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        requirement: Option<String>,
+    },
+    TrainPlace {
+        produces: u32,
+    },
+}
+
+impl AbilityTarget {
+    /// Unit type created by the ability
+    pub fn produces(&self) -> Option<u32> {
+        match *self {
+            Self::Morph { produces }
+            | Self::MorphPlace { produces }
+            | Self::Build { produces }
+            | Self::BuildInstant { produces }
+            | Self::BuildOnUnit { produces }
+            | Self::Train { produces, .. }
+            | Self::TrainPlace { produces } => Some(produces),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpgradeData {
+    pub id: u32,
+    pub name: String,
+    pub cost: Cost,
+}
+
+/// Writes whole numbers without a fractional part, as the file does
+fn whole<S: serde::Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f32 {
+        serializer.serialize_i64(*value as i64)
+    } else {
+        serializer.serialize_f32(*value)
+    }
+}
+
+fn whole_opt<S: serde::Serializer>(value: &Option<f32>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => whole(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Protocol enums stored by name
+pub(crate) trait ProtoName: Sized {
+    fn as_str_name(&self) -> &'static str;
+    fn from_str_name(name: &str) -> Option<Self>;
+}
+
+macro_rules! impl_proto_name {
+    ($($ty:ty),+) => {
+        $(
+        impl ProtoName for $ty {
+            fn as_str_name(&self) -> &'static str {
+                <$ty>::as_str_name(self)
+            }
+            fn from_str_name(name: &str) -> Option<Self> {
+                <$ty>::from_str_name(name)
+            }
+        }
+        )+
+    };
+}
+
+impl_proto_name!(Race, Attribute, TargetType);
+
+mod proto_name {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    use super::ProtoName;
+
+    pub fn serialize<T: ProtoName, S: Serializer>(value: &T, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(value.as_str_name())
+    }
+    pub fn deserialize<'de, T: ProtoName, D: Deserializer<'de>>(d: D) -> Result<T, D::Error> {
+        let name = String::deserialize(d)?;
+        T::from_str_name(&name).ok_or_else(|| D::Error::custom(format!("unknown name {name}")))
+    }
+}
+
+mod proto_names {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    use super::ProtoName;
+
+    pub fn serialize<T: ProtoName, S: Serializer>(values: &[T], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(values.iter().map(ProtoName::as_str_name))
+    }
+    pub fn deserialize<'de, T: ProtoName, D: Deserializer<'de>>(d: D) -> Result<Vec<T>, D::Error> {
+        Vec::<String>::deserialize(d)?
+            .into_iter()
+            .map(|name| {
+                T::from_str_name(&name)
+                    .ok_or_else(|| D::Error::custom(format!("unknown name {name}")))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,236 @@
+//! Static game data: unit stats, abilities and upgrades.
+//!
+//! [`GameData`] is loaded from `entities.json` (bundled with the crate or from a file) or built
+//! from a live `ResponseData`. Lookups take any id convertible to `u32`, typed ids such as
+//! [`UnitTypeId`](crate::UnitTypeId) or the raw ids of observations.
+mod entities;
+mod response;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use rsc2_pb::protocol;
+
+use crate::Result;
+pub use entities::{
+    AbilityData, AbilityTarget, Cost, DamageBonus, Entities, Requirement, UnitAbility, UnitData,
+    UpgradeData, Weapon,
+};
+
+const BUNDLED: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/entities.json"));
+
+/// Damage dealt by a single hit, hits never deal less than this after armor
+const MIN_DAMAGE: f32 = 0.5;
+
+impl Weapon {
+    /// Damage of one attack against `target`, counting bonuses and armor
+    pub fn damage_against(&self, target: &UnitData) -> f32 {
+        let bonus: f32 = self
+            .bonuses
+            .iter()
+            .filter(|bonus| target.attributes.contains(&bonus.against))
+            .map(|bonus| bonus.damage)
+            .sum();
+        (self.damage_per_hit + bonus - target.armor).max(MIN_DAMAGE) * self.attacks as f32
+    }
+    pub fn dps_against(&self, target: &UnitData) -> f32 {
+        if self.cooldown > 0.0 {
+            self.damage_against(target) / self.cooldown
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Game data indexed by id
+#[derive(Debug, Clone, Default)]
+pub struct GameData {
+    units: BTreeMap<u32, UnitData>,
+    abilities: BTreeMap<u32, AbilityData>,
+    upgrades: BTreeMap<u32, UpgradeData>,
+}
+
+impl From<Entities> for GameData {
+    fn from(entities: Entities) -> Self {
+        Self {
+            units: entities.unit.into_iter().map(|u| (u.id, u)).collect(),
+            abilities: entities.ability.into_iter().map(|a| (a.id, a)).collect(),
+            upgrades: entities.upgrade.into_iter().map(|u| (u.id, u)).collect(),
+        }
+    }
+}
+
+/// Data reported by the api, which leaves out health, shields, energy, footprints, placement
+/// rules and the abilities of each unit: `max_health` is `0`, the optional fields are `None` and
+/// [`GameData::abilities_of`] yields nothing. The `refresh_entities` example merges it into the
+/// bundled data instead.
+impl From<&protocol::ResponseData> for GameData {
+    fn from(data: &protocol::ResponseData) -> Self {
+        Self::from(response::entities(data))
+    }
+}
+
+impl GameData {
+    /// Data of the `entities.json` shipped with the crate
+    pub fn bundled() -> Self {
+        Self::from_json(BUNDLED).expect("bundled entities.json is valid")
+    }
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(Self::from(serde_json::from_str::<Entities>(json)?))
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+    /// Records sorted by id, in the layout of `entities.json`
+    pub fn to_entities(&self) -> Entities {
+        Entities {
+            ability: self.abilities.values().cloned().collect(),
+            unit: self.units.values().cloned().collect(),
+            upgrade: self.upgrades.values().cloned().collect(),
+        }
+    }
+
+    pub fn unit(&self, id: impl Into<u32>) -> Option<&UnitData> {
+        self.units.get(&id.into())
+    }
+    pub fn ability(&self, id: impl Into<u32>) -> Option<&AbilityData> {
+        self.abilities.get(&id.into())
+    }
+    pub fn upgrade(&self, id: impl Into<u32>) -> Option<&UpgradeData> {
+        self.upgrades.get(&id.into())
+    }
+    pub fn units(&self) -> impl Iterator<Item = &UnitData> {
+        self.units.values()
+    }
+    pub fn abilities(&self) -> impl Iterator<Item = &AbilityData> {
+        self.abilities.values()
+    }
+    pub fn upgrades(&self) -> impl Iterator<Item = &UpgradeData> {
+        self.upgrades.values()
+    }
+
+    /// Abilities `unit` can use, whether or not their requirements are met
+    pub fn abilities_of(&self, unit: impl Into<u32>) -> impl Iterator<Item = &AbilityData> {
+        self.unit(unit)
+            .into_iter()
+            .flat_map(|unit| &unit.abilities)
+            .filter_map(|ability| self.abilities.get(&ability.ability))
+    }
+
+    /// Ability training, building or morphing into `unit`.
+    ///
+    /// Several abilities can produce the same unit type, trains and builds are preferred over
+    /// morphs, and morphs over their placed variants (warp-ins, landings). Remaining ties go to
+    /// the ability with a known cost then to the lowest id, which picks morphing a zergling into
+    /// a baneling over unburrowing one.
+    pub fn producer(&self, unit: impl Into<u32>) -> Option<&AbilityData> {
+        let unit = unit.into();
+        self.abilities
+            .values()
+            .filter(|ability| ability.target.produces() == Some(unit))
+            .min_by_key(|ability| {
+                let rank = match ability.target {
+                    AbilityTarget::Morph { .. } => 1,
+                    AbilityTarget::TrainPlace { .. } | AbilityTarget::MorphPlace { .. } => 2,
+                    _ => 0,
+                };
+                (rank, ability.cost.is_zero(), ability.id)
+            })
+    }
+
+    /// Cost of the [`producer`](Self::producer) of `unit`, `None` if its cost is unknown
+    pub fn cost(&self, unit: impl Into<u32>) -> Option<Cost> {
+        self.producer(unit)
+            .map(|ability| ability.cost)
+            .filter(|cost| !cost.is_zero())
+    }
+
+    /// Damage per second of the best weapon of `attacker` against `target`.
+    ///
+    /// Weapons are not filtered on whether the target is flying, the data doesn't tell.
+    pub fn dps_against(&self, attacker: impl Into<u32>, target: impl Into<u32>) -> f32 {
+        let (Some(attacker), Some(target)) = (self.unit(attacker), self.unit(target)) else {
+            return 0.0;
+        };
+        attacker
+            .weapons
+            .iter()
+            .map(|weapon| weapon.dps_against(target))
+            .fold(0.0, f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AbilityId, UnitTypeId};
+
+    #[test]
+    fn test_bundled() {
+        let data = GameData::bundled();
+        assert_eq!(data.units().count(), UnitTypeId::ALL.len());
+        assert_eq!(data.abilities().count(), AbilityId::ALL.len());
+
+        let marine = data.unit(UnitTypeId::Marine).unwrap();
+        assert_eq!(marine.weapons[0].damage_per_hit, 6.0);
+        assert!(
+            data.abilities_of(UnitTypeId::SCV)
+                .any(|ability| ability.id == u32::from(AbilityId::ATTACK_ATTACK))
+        );
+
+        let producer = |unit| data.producer(unit).map(|ability| ability.id);
+        assert_eq!(
+            producer(UnitTypeId::Zealot),
+            Some(AbilityId::GATEWAYTRAIN_ZEALOT.into())
+        );
+        assert_eq!(
+            producer(UnitTypeId::OrbitalCommand),
+            Some(AbilityId::UPGRADETOORBITAL_ORBITALCOMMAND.into())
+        );
+        assert_eq!(
+            producer(UnitTypeId::Baneling),
+            Some(AbilityId::MORPHZERGLINGTOBANELING_BANELING.into())
+        );
+
+        // marauders deal 10 + 10 vs armored, stalkers have 1 armor
+        let stalker = data.unit(UnitTypeId::Stalker).unwrap();
+        let marauder = data.unit(UnitTypeId::Marauder).unwrap();
+        assert_eq!(marauder.weapons[0].damage_against(stalker), 19.0);
+        assert!(
+            data.dps_against(UnitTypeId::Marauder, UnitTypeId::Stalker)
+                > data.dps_against(UnitTypeId::Marauder, UnitTypeId::Zergling)
+        );
+    }
+
+    #[test]
+    fn test_cost() {
+        let mut entities = GameData::bundled().to_entities();
+        let mut set_cost = |id: AbilityId, minerals, time| {
+            let ability = entities
+                .ability
+                .iter_mut()
+                .find(|ability| ability.id == u32::from(id))
+                .unwrap();
+            ability.cost = Cost {
+                minerals,
+                gas: 0,
+                time,
+            };
+        };
+        set_cost(AbilityId::GATEWAYTRAIN_ZEALOT, 100, 27.0);
+        set_cost(AbilityId::WARPGATETRAIN_ZEALOT, 100, 20.0);
+        // landing is not how an orbital command is made, its cost is left unknown
+        set_cost(AbilityId::LAND_ORBITALCOMMAND, 150, 25.0);
+        let data = GameData::from(entities);
+
+        assert_eq!(data.cost(UnitTypeId::Zealot).unwrap().time, 27.0);
+        assert_eq!(data.cost(UnitTypeId::OrbitalCommand), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let entities = GameData::bundled().to_entities();
+        let json = serde_json::to_string(&entities).unwrap();
+        assert_eq!(serde_json::from_str::<Entities>(&json).unwrap(), entities);
+    }
+}
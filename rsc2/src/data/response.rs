@@ -0,0 +1,200 @@
+//! Records built from a live `ResponseData`.
+//!
+//! The api doesn't report health, shields, energy, footprints, placement rules nor the
+//! abilities of each unit, those fields are left empty.
+use std::collections::BTreeMap;
+
+use rsc2_pb::protocol::{self, Attribute, ability_data::Target};
+
+use super::entities::{
+    AbilityData, AbilityTarget, Cost, DamageBonus, Entities, UnitData, UpgradeData, Weapon,
+};
+use crate::UnitTypeId;
+
+const WORKERS: [UnitTypeId; 3] = [UnitTypeId::SCV, UnitTypeId::Probe, UnitTypeId::Drone];
+const TOWNHALLS: [UnitTypeId; 7] = [
+    UnitTypeId::CommandCenter,
+    UnitTypeId::OrbitalCommand,
+    UnitTypeId::PlanetaryFortress,
+    UnitTypeId::Nexus,
+    UnitTypeId::Hatchery,
+    UnitTypeId::Lair,
+    UnitTypeId::Hive,
+];
+
+pub(super) fn entities(data: &protocol::ResponseData) -> Entities {
+    let units: Vec<_> = data
+        .units
+        .iter()
+        .filter(|unit| unit.available() && !unit.name().is_empty())
+        .collect();
+    let upgrades: Vec<_> = data
+        .upgrades
+        .iter()
+        .filter(|upgrade| !upgrade.name().is_empty())
+        .collect();
+
+    let produced: BTreeMap<u32, &protocol::UnitTypeData> = units
+        .iter()
+        .filter(|unit| unit.ability_id() != 0)
+        .map(|unit| (unit.ability_id(), *unit))
+        .collect();
+    let researched: BTreeMap<u32, &protocol::UpgradeData> = upgrades
+        .iter()
+        .filter(|upgrade| upgrade.ability_id() != 0)
+        .map(|upgrade| (upgrade.ability_id(), *upgrade))
+        .collect();
+
+    Entities {
+        ability: data
+            .abilities
+            .iter()
+            .filter(|ability| ability.available())
+            .map(|data| {
+                let id = data.ability_id();
+                ability(data, produced.get(&id), researched.get(&id))
+            })
+            .collect(),
+        unit: units.into_iter().map(unit).collect(),
+        upgrade: upgrades.into_iter().map(upgrade).collect(),
+    }
+}
+
+fn unit(data: &protocol::UnitTypeData) -> UnitData {
+    let attributes: Vec<_> = data
+        .attributes
+        .iter()
+        .filter_map(|&attribute| Attribute::try_from(attribute).ok())
+        .collect();
+    let typed = UnitTypeId::try_from(data.unit_id()).ok();
+    UnitData {
+        id: data.unit_id(),
+        name: data.name().into(),
+        race: data.race(),
+        supply: data.food_required(),
+        cargo_size: Some(data.cargo_size()).filter(|&size| size > 0),
+        max_health: 0.0,
+        armor: data.armor(),
+        sight: data.sight_range(),
+        speed: Some(data.movement_speed()).filter(|&speed| speed > 0.0),
+        speed_creep_mul: 1.0,
+        is_structure: attributes.contains(&Attribute::Structure),
+        attributes,
+        size: 0,
+        radius: None,
+        accepts_addon: false,
+        needs_power: false,
+        needs_creep: false,
+        needs_gayser: false,
+        is_addon: data.require_attached(),
+        is_worker: typed.is_some_and(|id| WORKERS.contains(&id)),
+        is_townhall: typed.is_some_and(|id| TOWNHALLS.contains(&id)),
+        max_shield: None,
+        weapons: data.weapons.iter().map(weapon).collect(),
+        abilities: Vec::new(),
+        max_energy: None,
+        start_energy: None,
+        normal_mode: None,
+        cargo_capacity: None,
+        detection_range: None,
+        power_radius: None,
+    }
+}
+
+fn weapon(data: &protocol::Weapon) -> Weapon {
+    Weapon {
+        target_type: data.r#type(),
+        damage_per_hit: data.damage(),
+        damage_splash: 0.0,
+        attacks: data.attacks(),
+        range: data.range(),
+        cooldown: data.speed(),
+        bonuses: data
+            .damage_bonus
+            .iter()
+            .map(|bonus| DamageBonus {
+                against: bonus.attribute(),
+                damage: bonus.bonus(),
+            })
+            .collect(),
+    }
+}
+
+/// `Attack Attack` becomes `ATTACK_ATTACK`
+fn ability_name(data: &protocol::AbilityData) -> String {
+    let name = match data.friendly_name() {
+        "" => data.link_name(),
+        name => name,
+    };
+    name.to_uppercase().replace(' ', "_")
+}
+
+fn ability(
+    data: &protocol::AbilityData,
+    produces: Option<&&protocol::UnitTypeData>,
+    researches: Option<&&protocol::UpgradeData>,
+) -> AbilityData {
+    let (target, cost) = match (researches, produces) {
+        (Some(upgrade), _) => (
+            AbilityTarget::Research {
+                upgrade: upgrade.upgrade_id(),
+            },
+            upgrade_cost(upgrade),
+        ),
+        (None, Some(unit)) => {
+            let produces = unit.unit_id();
+            let target = if data.is_building() {
+                AbilityTarget::Build { produces }
+            } else {
+                AbilityTarget::Train {
+                    produces,
+                    requirement: None,
+                }
+            };
+            let cost = Cost {
+                minerals: unit.mineral_cost(),
+                gas: unit.vespene_cost(),
+                time: unit.build_time(),
+            };
+            (target, cost)
+        }
+        (None, None) => {
+            let target = match data.target() {
+                Target::None | Target::PointOrNone => AbilityTarget::None,
+                Target::Point => AbilityTarget::Point,
+                Target::Unit => AbilityTarget::Unit,
+                Target::PointOrUnit => AbilityTarget::PointOrUnit,
+            };
+            (target, Cost::default())
+        }
+    };
+    AbilityData {
+        id: data.ability_id(),
+        name: ability_name(data),
+        cast_range: data.cast_range(),
+        energy_cost: 0.0,
+        allow_minimap: data.allow_minimap(),
+        allow_autocast: data.allow_autocast(),
+        effect: Vec::new(),
+        buff: Vec::new(),
+        cooldown: 0.0,
+        target,
+        cost,
+    }
+}
+
+fn upgrade_cost(data: &protocol::UpgradeData) -> Cost {
+    Cost {
+        minerals: data.mineral_cost(),
+        gas: data.vespene_cost(),
+        time: data.research_time(),
+    }
+}
+
+fn upgrade(data: &protocol::UpgradeData) -> UpgradeData {
+    UpgradeData {
+        id: data.upgrade_id(),
+        name: data.name().into(),
+        cost: upgrade_cost(data),
+    }
+}
@@ -18,6 +18,7 @@ where
     race: Race,
     kind: protocol::PlayerType,
     difficulty: Option<Difficulty>,
+    build: Option<protocol::AiBuild>,
 }
 
 impl<T> Player<T>
@@ -30,6 +31,7 @@ where
             race,
             kind: protocol::PlayerType::Participant,
             difficulty: None,
+            build: None,
         }
     }
     pub fn bot(name: T, race: Race, difficulty: Difficulty) -> Self {
@@ -38,24 +40,33 @@ where
             race,
             kind: protocol::PlayerType::Computer,
             difficulty: Some(difficulty),
+            build: None,
         }
     }
+    /// Strategy of a computer player, the game picks one at random by default
+    pub fn with_build(mut self, build: protocol::AiBuild) -> Self {
+        self.build = Some(build);
+        self
+    }
 }
 
-impl<T> Into<protocol::PlayerSetup> for Player<T>
+impl<T> From<Player<T>> for protocol::PlayerSetup
 where
     T: Into<String>,
 {
-    fn into(self) -> protocol::PlayerSetup {
+    fn from(player: Player<T>) -> Self {
         let mut s = protocol::PlayerSetup {
-            player_name: Some(self.name.into()),
+            player_name: Some(player.name.into()),
             ..Default::default()
         };
-        s.set_type(self.kind);
-        s.set_race(self.race);
-        if let Some(difficulty) = self.difficulty {
+        s.set_type(player.kind);
+        s.set_race(player.race);
+        if let Some(difficulty) = player.difficulty {
             s.set_difficulty(difficulty);
         }
+        if let Some(build) = player.build {
+            s.set_ai_build(build);
+        }
         s
     }
 }
@@ -81,7 +92,7 @@ impl ToMapRef for protocol::request_create_game::Map {
     }
 }
 
-impl<'a> ToMapRef for &'a str {
+impl ToMapRef for &str {
     fn to_map(self) -> protocol::request_create_game::Map {
         from_path(std::path::Path::new(self))
     }
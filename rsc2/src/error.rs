@@ -0,0 +1,81 @@
+use std::io;
+use std::net::SocketAddr;
+use std::process::ExitStatus;
+
+use rsc2_pb::protocol::{self, Status};
+
+use crate::action::ActionResults;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors of the runtime, api failures carry the id of the response that reported them.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("invalid api address")]
+    InvalidAddress,
+    #[error("invalid ladder arguments: {0}")]
+    LadderArgument(String),
+    #[error("connection to the api closed")]
+    Closed,
+    /// Failure of the connection a request was waiting on, the error itself is returned by the
+    /// [`Driver`](crate::mux::Driver)
+    #[error("connection to the api failed: {0}")]
+    Disconnected(String),
+    #[error("SC2 process exited before accepting connections: {0}")]
+    ProcessExited(ExitStatus),
+    #[error("SC2 api on {0} did not come up")]
+    ApiTimeout(SocketAddr),
+    #[error("state machine is not in the {expected} state")]
+    InvalidState { expected: &'static str },
+    #[error("response id: {id} | server status: {status:?}")]
+    Status { id: u32, status: Status },
+    #[error("response id: {id} | err: {errors:?}")]
+    Response { id: u32, errors: Vec<String> },
+    #[error("response id: {id} | expected a response to request id {expected}")]
+    MismatchedId { id: u32, expected: u32 },
+    #[error("response id: {id} | expected {expected} response")]
+    Unexpected { id: u32, expected: &'static str },
+    #[error("response id: {id} | no request was sent with this id")]
+    Orphaned { id: u32 },
+    /// Actions sent with [`Client::checked_actions`](crate::Client::checked_actions) that the
+    /// game refused, along with the results of the others
+    #[error("response id: {id} | actions refused: {results:?}")]
+    Action { id: u32, results: ActionResults },
+    #[error("response id: {id} | create game: {error:?} {details}")]
+    CreateGame {
+        id: u32,
+        error: protocol::response_create_game::Error,
+        details: String,
+    },
+    #[error("response id: {id} | join game: {error:?} {details}")]
+    JoinGame {
+        id: u32,
+        error: protocol::response_join_game::Error,
+        details: String,
+    },
+    #[error("response id: {id} | start replay: {error:?} {details}")]
+    StartReplay {
+        id: u32,
+        error: protocol::response_start_replay::Error,
+        details: String,
+    },
+    #[error("response id: {id} | restart game: {error:?} {details}")]
+    RestartGame {
+        id: u32,
+        error: protocol::response_restart_game::Error,
+        details: String,
+        need_hard_reset: bool,
+    },
+    #[error("response id: {id} | replay info: {error:?} {details}")]
+    ReplayInfo {
+        id: u32,
+        error: protocol::response_replay_info::Error,
+        details: String,
+    },
+    #[error("unknown {kind} id {id}")]
+    UnknownId { kind: &'static str, id: u32 },
+}
@@ -0,0 +1,69 @@
+//! Plain points without the optional fields of the protocol messages.
+use rsc2_pb::protocol;
+
+/// A position on the map
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Point2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point2 {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+    pub fn distance_squared(self, other: impl Into<Point2>) -> f32 {
+        let other = other.into();
+        (self.x - other.x).powi(2) + (self.y - other.y).powi(2)
+    }
+    pub fn distance(self, other: impl Into<Point2>) -> f32 {
+        self.distance_squared(other).sqrt()
+    }
+}
+
+/// A position on the map along with its height
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Point3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Point3 {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl From<(f32, f32)> for Point2 {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Point3> for Point2 {
+    fn from(point: Point3) -> Self {
+        Self::new(point.x, point.y)
+    }
+}
+
+impl From<protocol::Point2D> for Point2 {
+    fn from(point: protocol::Point2D) -> Self {
+        Self::new(point.x(), point.y())
+    }
+}
+
+impl From<Point2> for protocol::Point2D {
+    fn from(point: Point2) -> Self {
+        Self {
+            x: Some(point.x),
+            y: Some(point.y),
+        }
+    }
+}
+
+impl From<protocol::Point> for Point3 {
+    fn from(point: protocol::Point) -> Self {
+        Self::new(point.x(), point.y(), point.z())
+    }
+}
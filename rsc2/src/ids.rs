@@ -0,0 +1,49 @@
+//! Unit type, ability and upgrade ids generated from `entities.json`.
+//!
+//! Variants are named after the entities, `UnitTypeId::SCV` or `AbilityId::ATTACK_ATTACK`.
+use std::fmt;
+
+use rsc2_pb::protocol::Race;
+
+use crate::Error;
+
+macro_rules! impl_id {
+    ($id:ident) => {
+        impl From<$id> for u32 {
+            fn from(id: $id) -> Self {
+                id as u32
+            }
+        }
+        impl From<$id> for i32 {
+            fn from(id: $id) -> Self {
+                id as i32
+            }
+        }
+        impl fmt::Display for $id {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.name())
+            }
+        }
+    };
+}
+
+include!(concat!(env!("OUT_DIR"), "/ids.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids() {
+        assert_eq!(UnitTypeId::try_from(45).unwrap(), UnitTypeId::SCV);
+        assert_eq!(UnitTypeId::SCV.race(), Race::Terran);
+        assert_eq!(u32::from(AbilityId::ATTACK_ATTACK), 23);
+        assert_eq!(AbilityId::ATTACK_ATTACK.race(), None);
+        assert_eq!(UpgradeId::Stimpack.race(), Some(Race::Terran));
+        assert_eq!(UpgradeId::Stimpack.to_string(), "Stimpack");
+        assert!(matches!(
+            UnitTypeId::try_from(u32::MAX),
+            Err(Error::UnknownId { id: u32::MAX, .. })
+        ));
+    }
+}
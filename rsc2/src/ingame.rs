@@ -2,48 +2,91 @@ use std::pin::Pin;
 use std::task::{Context, Poll};
 
 use crate::{
-    Connection,
+    Connection, Error, Result,
+    client::{ApiRequest, typed_response},
     state_machine::{Core, Ended, InGame},
 };
-use either::Either;
 
-use futures::io;
-use futures::{ready, sink::Sink, stream::Stream};
+use futures::{SinkExt, StreamExt, ready, sink::Sink, stream::Stream};
 use rsc2_pb::protocol::{self, Status};
 
 pub struct InGameListener<'sm, 'b> {
-    state: Either<Option<InGame<'sm>>, Ended<'sm>>,
+    state: InGame<'sm>,
     framed: Pin<&'b mut Connection>,
-}
-
-fn try_end_game<'sm>(
-    state: Either<Option<InGame<'sm>>, Ended<'sm>>,
-) -> Result<Ended<'sm>, io::Error> {
-    state.either(
-        |ingame| {
-            if let Some(mut ingame) = ingame {
-                ingame.core().replace(Core::Ended {});
-                Ok(Ended::from(ingame))
-            } else {
-                Err(io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    "Game is already ended",
-                ))
-            }
-        },
-        Ok,
-    )
+    /// A step or action ended the game, the final observation is still to be requested
+    stepped_to_end: bool,
+    ended: bool,
 }
 
 impl<'sm, 'b> InGameListener<'sm, 'b> {
     pub fn new(state: InGame<'sm>, framed: Pin<&'b mut Connection>) -> Self {
         Self {
-            state: Either::Left(Some(state)),
+            state,
             framed,
+            stepped_to_end: false,
+            ended: false,
+        }
+    }
+    /// Whether the server reported the game as ended
+    pub fn is_ended(&self) -> bool {
+        self.ended || self.stepped_to_end
+    }
+    /// Ended state of the game, a game that is still running is ended without results.
+    ///
+    /// The results of a game ended by a step or an action are only known once [`call`](Self::call)
+    /// returned `None`.
+    pub fn into_ended(mut self) -> Ended<'sm> {
+        if !self.ended {
+            self.state.core().replace(Core::Ended {
+                results: Vec::new(),
+            });
+        }
+        Ended::from(self.state)
+    }
+
+    /// Sends `request` and waits for its response, `None` once the game has ended.
+    pub async fn call<R: ApiRequest>(&mut self, request: R) -> Result<Option<R::Response>> {
+        let request: protocol::Request = request.into();
+        self.send(request).await?;
+        let sent = self.framed.codec().id();
+        let Some(res) = self.next().await else {
+            if self.stepped_to_end {
+                self.final_observation().await?;
+            }
+            return Ok(None);
+        };
+        let res = res?;
+        if res.id.is_some_and(|id| id != sent) {
+            return Err(Error::MismatchedId {
+                id: res.id(),
+                expected: sent,
+            });
+        }
+        typed_response::<R>(res).map(|(_, response)| Some(response))
+    }
+
+    /// Requests the observation following the step or action that ended the game, only this
+    /// observation carries the results of the players.
+    async fn final_observation(&mut self) -> Result<()> {
+        self.stepped_to_end = false;
+        self.send(protocol::RequestObservation::default().into())
+            .await?;
+        // an observation reported as Ended is handled by `poll_next`
+        if let Some(res) = self.next().await {
+            let results = match res?.response {
+                Some(protocol::response::Response::Observation(observation)) => {
+                    observation.player_result
+                }
+                _ => Vec::new(),
+            };
+            self.end(results);
         }
+        Ok(())
     }
-    pub fn into_ended(self) -> Ended<'sm> {
-        try_end_game(self.state).unwrap()
+
+    fn end(&mut self, results: Vec<protocol::PlayerResult>) {
+        self.ended = true;
+        self.state.core().replace(Core::Ended { results });
     }
 }
 
@@ -51,7 +94,7 @@ impl<'sm, 'b> Stream for InGameListener<'sm, 'b> {
     type Item = <Connection as Stream>::Item;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if self.state.is_right() {
+        if self.ended {
             return Poll::Ready(None);
         }
 
@@ -64,9 +107,16 @@ impl<'sm, 'b> Stream for InGameListener<'sm, 'b> {
             )
         });
         if match_ended {
-            let state = std::mem::replace(&mut self.state, Either::Left(None));
-            self.state = Either::Right(try_end_game(state).unwrap());
-
+            // the observation ending the game carries the results of the players
+            match response {
+                Some(Ok(protocol::Response {
+                    response: Some(protocol::response::Response::Observation(observation)),
+                    ..
+                })) => self.end(observation.player_result),
+                // in step mode the game ends on a step or an action, the results are carried
+                // by the observation that follows
+                _ => self.stepped_to_end = true,
+            }
             return Poll::Ready(None);
         }
 
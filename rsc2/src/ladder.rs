@@ -0,0 +1,192 @@
+//! Games run by a ladder manager, following the AI Arena conventions.
+//!
+//! The ladder launches the instances and creates the game, each bot is then started with
+//! `--LadderServer <host> --GamePort <port> --StartPort <port> [--OpponentId <id>] [--RealTime]`
+//! and only joins the game running on `LadderServer:GamePort`.
+use std::net::ToSocketAddrs;
+
+use rsc2_pb::protocol;
+
+use crate::{
+    Agent, Connection, Core, Error, InGame, Result, StepMode, agent, connect_s2api,
+    multiplayer::{GamePorts, participant_join},
+};
+
+/// Arguments given by the ladder manager
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LadderArgs {
+    /// Host of the instance the bot plays on
+    pub ladder_server: String,
+    /// Api port of the instance the bot plays on
+    pub game_port: u16,
+    /// First port of the range shared by the instances, see [`GamePorts::contiguous`]
+    pub start_port: u16,
+    /// Id of the opponent on the ladder, to adapt to an opponent seen before
+    pub opponent_id: Option<String>,
+    pub realtime: bool,
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::LadderArgument(message.into())
+}
+
+impl LadderArgs {
+    /// Parses the arguments of the process, `None` if it wasn't started by a ladder
+    pub fn from_env() -> Result<Option<Self>> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    /// Parses ladder arguments, `None` without a `--LadderServer` argument so the process can
+    /// fall back to its own arguments. Unknown arguments are skipped with a warning.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>> {
+        let args: Vec<String> = args.into_iter().collect();
+        if !args.iter().any(|arg| arg == "--LadderServer") {
+            return Ok(None);
+        }
+        let mut args = args.into_iter();
+
+        let (mut ladder_server, mut game_port, mut start_port) = (None, None, None);
+        let mut opponent_id = None;
+        let mut realtime = false;
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| invalid(format!("{arg} <value>")));
+            match arg.as_str() {
+                "--LadderServer" => ladder_server = Some(value()?),
+                "--GamePort" => game_port = Some(value()?),
+                "--StartPort" => start_port = Some(value()?),
+                "--OpponentId" => opponent_id = Some(value()?),
+                "--RealTime" => realtime = true,
+                // managers pass arguments meant for other bots such as `--ComputerOpponent`
+                other => warn!("ignoring unknown ladder argument {other}"),
+            }
+        }
+
+        let port = |name, value: Option<String>| {
+            value
+                .ok_or_else(|| invalid(format!("--{name} is required")))?
+                .parse::<u16>()
+                .map_err(|e| invalid(format!("--{name}: {e}")))
+        };
+        Ok(Some(Self {
+            ladder_server: ladder_server.ok_or_else(|| invalid("--LadderServer is required"))?,
+            game_port: port("GamePort", game_port)?,
+            start_port: port("StartPort", start_port)?,
+            opponent_id,
+            realtime,
+        }))
+    }
+
+    /// Address of the api of the instance the bot plays on
+    pub fn addr(&self) -> impl ToSocketAddrs + '_ {
+        (self.ladder_server.as_str(), self.game_port)
+    }
+
+    /// Ports of a game between two bots
+    pub fn ports(&self) -> GamePorts {
+        GamePorts::contiguous(self.start_port.into(), 1)
+    }
+
+    /// [`StepMode::Realtime`] if the ladder asked for a realtime game, [`StepMode::Step`]
+    /// otherwise
+    pub fn step_mode(&self, count: u32) -> StepMode {
+        if self.realtime {
+            StepMode::Realtime(count)
+        } else {
+            StepMode::Step(count)
+        }
+    }
+}
+
+/// Joins the game created by the ladder as `player`
+pub async fn join_game<'core>(
+    core: &'core mut Core,
+    args: &LadderArgs,
+    player: impl Into<protocol::PlayerSetup>,
+) -> Result<(InGame<'core>, Connection)> {
+    let state = core.launched().ok_or(Error::InvalidState {
+        expected: "Launched",
+    })?;
+
+    let mut connection = connect_s2api(args.addr()).await?;
+    let join_game = participant_join(&player.into(), &args.ports());
+    let state = state.join_game(&mut connection, join_game).await?;
+
+    Ok((state, connection))
+}
+
+/// Joins the game created by the ladder and plays `agent` until it ends.
+///
+/// `step` is the number of game loops between two observations, the game is realtime if the
+/// ladder passed `--RealTime`.
+pub async fn run_game<A: Agent>(
+    core: &mut Core,
+    args: &LadderArgs,
+    player: impl Into<protocol::PlayerSetup>,
+    step: u32,
+    agent: &mut A,
+) -> Result<Vec<protocol::PlayerResult>> {
+    let (state, mut connection) = join_game(core, args, player).await?;
+    agent::play(state, &mut connection, args.step_mode(step), agent).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<LadderArgs>> {
+        LadderArgs::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse() {
+        let args = parse(&[
+            "--GamePort",
+            "5677",
+            "--StartPort",
+            "5690",
+            "--LadderServer",
+            "127.0.0.1",
+            "--OpponentId",
+            "b4d7dc43-3237-446f-bed1-bceae0868e89",
+            "--RealTime",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(args.game_port, 5677);
+        assert_eq!(
+            args.opponent_id.as_deref(),
+            Some("b4d7dc43-3237-446f-bed1-bceae0868e89")
+        );
+        assert_eq!(args.step_mode(2), StepMode::Realtime(2));
+        assert_eq!(args.ports(), GamePorts::contiguous(5690, 1));
+
+        assert_eq!(parse(&["--config", "bot.toml"]).unwrap(), None);
+        let args = parse(&[
+            "--LadderServer",
+            "127.0.0.1",
+            "--GamePort",
+            "5677",
+            "--StartPort",
+            "5690",
+            "--ComputerOpponent",
+            "--OpponentId",
+            "1",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(args.opponent_id.as_deref(), Some("1"));
+        assert!(parse(&["--LadderServer", "127.0.0.1", "--StartPort", "5690"]).is_err());
+        assert!(parse(&["--LadderServer", "127.0.0.1", "--GamePort"]).is_err());
+        assert!(
+            parse(&[
+                "--LadderServer",
+                "x",
+                "--GamePort",
+                "port",
+                "--StartPort",
+                "1"
+            ])
+            .is_err()
+        );
+    }
+}
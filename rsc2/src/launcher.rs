@@ -0,0 +1,289 @@
+//! Spawns a local SC2 process and waits for its api to come up.
+use std::{
+    ffi::OsString,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    process::Stdio,
+    time::Duration,
+};
+
+use tokio::process::{Child, Command};
+
+use crate::{
+    Error, Result, connect_s2api,
+    state_machine::{Core, Launched},
+};
+
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Command line and readiness settings of a SC2 process
+#[derive(Debug, Clone)]
+pub struct LaunchOptions {
+    binary: PathBuf,
+    listen: IpAddr,
+    port: u16,
+    data_dir: Option<PathBuf>,
+    temp_dir: Option<PathBuf>,
+    working_dir: Option<PathBuf>,
+    extra_args: Vec<OsString>,
+    attempts: u32,
+    backoff: Duration,
+}
+
+impl LaunchOptions {
+    pub fn new(binary: impl Into<PathBuf>) -> Self {
+        Self {
+            binary: binary.into(),
+            listen: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8167,
+            data_dir: None,
+            temp_dir: None,
+            working_dir: None,
+            extra_args: Vec::new(),
+            attempts: 60,
+            backoff: Duration::from_millis(250),
+        }
+    }
+    /// Address the api will listen on (`-listen`)
+    pub fn listen(mut self, listen: IpAddr) -> Self {
+        self.listen = listen;
+        self
+    }
+    /// Port the api will listen on (`-port`)
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+    /// Game data directory (`-dataDir`)
+    pub fn data_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.data_dir = Some(dir.into());
+        self
+    }
+    /// Directory for temporary files (`-tempDir`)
+    pub fn temp_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = Some(dir.into());
+        self
+    }
+    /// Working directory of the process, SC2 expects to be started from its `Support64` folder
+    pub fn working_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+    /// Appends a raw argument to the command line
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.extra_args.push(arg.into());
+        self
+    }
+    /// Number of connection attempts and delay before the first retry, the delay doubles on
+    /// every attempt.
+    pub fn retry(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.attempts = attempts.max(1);
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.listen, self.port)
+    }
+
+    /// Arguments passed to the binary
+    pub fn args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![
+            "-listen".into(),
+            self.listen.to_string().into(),
+            "-port".into(),
+            self.port.to_string().into(),
+        ];
+        if let Some(dir) = &self.data_dir {
+            args.push("-dataDir".into());
+            args.push(dir.into());
+        }
+        if let Some(dir) = &self.temp_dir {
+            args.push("-tempDir".into());
+            args.push(dir.into());
+        }
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+
+    /// Spawns the process and resolves once the `/sc2api` websocket accepts connections.
+    ///
+    /// The probe connection is closed before returning, the api is free to be connected to.
+    pub async fn launch(self) -> Result<Instance> {
+        let mut command = Command::new(&self.binary);
+        command
+            .args(self.args())
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        if let Some(dir) = &self.working_dir {
+            command.current_dir(dir);
+        }
+
+        info!("launching {} on {}", self.binary.display(), self.addr());
+        let child = command.spawn()?;
+        let mut instance = Instance {
+            child,
+            addr: self.addr(),
+            core: Core::init(),
+        };
+        instance.wait_ready(self.attempts, self.backoff).await?;
+        Ok(instance)
+    }
+}
+
+/// A running SC2 process, killed when dropped
+#[derive(Debug)]
+pub struct Instance {
+    child: Child,
+    addr: SocketAddr,
+    core: Core,
+}
+
+impl Instance {
+    async fn wait_ready(&mut self, attempts: u32, mut backoff: Duration) -> Result<()> {
+        for attempt in 1..=attempts {
+            if let Some(status) = self.child.try_wait()? {
+                return Err(Error::ProcessExited(status));
+            }
+            match connect_s2api(self.addr).await {
+                Ok(_probe) => {
+                    info!(
+                        "SC2 api ready on {} after {} attempt(s)",
+                        self.addr, attempt
+                    );
+                    return Ok(());
+                }
+                Err(e) => debug!("attempt {}/{}: {}", attempt, attempts, e),
+            }
+            if attempt < attempts {
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+        Err(Error::ApiTimeout(self.addr))
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+    /// Os process id, `None` once the process has been reaped
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+    pub fn core(&mut self) -> &mut Core {
+        &mut self.core
+    }
+    pub fn launched(&mut self) -> Option<Launched<'_>> {
+        self.core.launched()
+    }
+    /// Kills the process and waits for it to exit
+    pub async fn kill(&mut self) -> Result<()> {
+        Ok(self.child.kill().await?)
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        if let Err(e) = self.child.start_kill() {
+            debug!("SC2 process already stopped: {}", e);
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Shell script standing in for the game binary, its directory is removed on drop
+    struct FakeSc2 {
+        dir: PathBuf,
+    }
+
+    impl FakeSc2 {
+        fn new(name: &str, body: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("rsc2-launcher-{}-{}", std::process::id(), name));
+            std::fs::create_dir_all(&dir).unwrap();
+            let fake = Self { dir };
+            let binary = fake.binary();
+            std::fs::write(&binary, format!("#!/bin/sh\n{body}\n")).unwrap();
+            std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+            fake
+        }
+        fn binary(&self) -> PathBuf {
+            self.dir.join("SC2_x64")
+        }
+    }
+
+    impl Drop for FakeSc2 {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[test]
+    fn test_args() {
+        let options = LaunchOptions::new("SC2_x64")
+            .port(9000)
+            .data_dir("/opt/sc2")
+            .temp_dir("/tmp/sc2")
+            .arg("-displayMode")
+            .arg("0");
+        assert_eq!(
+            options.args(),
+            [
+                "-listen",
+                "127.0.0.1",
+                "-port",
+                "9000",
+                "-dataDir",
+                "/opt/sc2",
+                "-tempDir",
+                "/tmp/sc2",
+                "-displayMode",
+                "0"
+            ]
+            .map(OsString::from)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_exits() {
+        let fake = FakeSc2::new("exits", "exit 3");
+        let err = LaunchOptions::new(fake.binary())
+            .port(free_port())
+            .retry(5, Duration::from_millis(20))
+            .launch()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ProcessExited(status) if status.code() == Some(3)));
+    }
+
+    #[tokio::test]
+    async fn test_api_never_ready() {
+        let fake = FakeSc2::new(
+            "hangs",
+            "echo \"$@\" > \"$(dirname \"$0\")/args\"\nexec sleep 30",
+        );
+        let err = LaunchOptions::new(fake.binary())
+            .port(free_port())
+            .retry(5, Duration::from_millis(20))
+            .launch()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ApiTimeout(_)));
+
+        let args = std::fs::read_to_string(fake.dir.join("args")).unwrap();
+        assert!(args.starts_with("-listen 127.0.0.1 -port "));
+    }
+}
@@ -11,44 +11,61 @@ use tokio_util::codec::Framed;
 use tokio_util::codec::FramedParts;
 use websocket_lite::ClientBuilder;
 
+pub mod action;
+pub mod agent;
+pub mod client;
+pub mod data;
 pub mod definitions;
+mod error;
+pub mod geometry;
+pub mod ids;
 mod ingame;
+pub mod ladder;
+pub mod launcher;
+pub mod multiplayer;
+pub mod mux;
 pub mod prelude;
+pub mod replay;
 pub mod state_machine;
+pub mod stepper;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod unit;
 
+pub use crate::action::{ActionResults, Actions, Command};
+pub use crate::agent::{Agent, run_game};
+pub use crate::client::Client;
+pub use crate::data::GameData;
 use crate::definitions::ToMapRef;
+pub use crate::error::{Error, Result};
+pub use crate::ids::{AbilityId, UnitTypeId, UpgradeId};
 pub use crate::state_machine::Core;
-pub use crate::state_machine::InGame;
+pub use crate::state_machine::{InGame, InReplay};
 pub use ingame::InGameListener;
+pub use replay::ReplayObserver;
+pub use stepper::{StepMode, Stepper};
+pub use unit::{Unit, Units};
 
 pub type Connection = Framed<TcpStream, S2Codec>;
 
-pub async fn connect_s2api(addr: impl std::net::ToSocketAddrs) -> io::Result<Connection> {
-    let addr = format!(
-        "ws://{}/sc2api",
-        addr.to_socket_addrs()
-            .ok()
-            .as_mut()
-            .and_then(Iterator::next)
-            .unwrap()
-    );
-    let client = ClientBuilder::new(&addr).map(ClientBuilder::async_connect_insecure);
-    match client {
-        Ok(client) => match client.await {
-            Ok(framed) => {
-                let FramedParts { io, codec, .. } = framed.into_parts();
-                Ok(Framed::from_parts(FramedParts::new::<
-                    rsc2_pb::protocol::Request,
-                >(
-                    io, S2Codec::from(codec)
-                )))
-            }
-            Err(e) => Err(io::Error::new(io::ErrorKind::ConnectionRefused, e)),
-        },
-        Err(_) => Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "invalid address",
-        )),
+pub async fn connect_s2api(addr: impl std::net::ToSocketAddrs) -> Result<Connection> {
+    let addr = addr
+        .to_socket_addrs()
+        .ok()
+        .as_mut()
+        .and_then(Iterator::next)
+        .ok_or(Error::InvalidAddress)?;
+    let client = ClientBuilder::new(&format!("ws://{}/sc2api", addr))
+        .map_err(|_| Error::InvalidAddress)?
+        .async_connect_insecure();
+    match client.await {
+        Ok(framed) => {
+            let FramedParts { io, codec, .. } = framed.into_parts();
+            Ok(Framed::from_parts(FramedParts::new::<
+                rsc2_pb::protocol::Request,
+            >(io, S2Codec::from(codec))))
+        }
+        Err(e) => Err(io::Error::new(io::ErrorKind::ConnectionRefused, e).into()),
     }
 }
 
@@ -69,42 +86,78 @@ pub async fn create_game<'core, P: Into<protocol::PlayerSetup>>(
     players: impl IntoIterator<Item = P>,
     map: impl ToMapRef,
     realtime: bool,
-) -> io::Result<(InGame<'core>, Connection)> {
+) -> Result<(InGame<'core>, Connection)> {
     let players: Vec<protocol::PlayerSetup> = players.into_iter().map(Into::into).collect();
 
     let participant_race = players
         .iter()
-        .find(|p| (p.r#type == Some(protocol::PlayerType::Participant as i32)))
-        .map(|p| p.race)
-        .flatten();
+        .find(|p| p.r#type == Some(protocol::PlayerType::Participant as i32))
+        .and_then(|p| p.race);
 
     // game assumed to be running
-    let state = core
-        .launched()
-        .ok_or(io::Error::from(io::ErrorKind::Interrupted))?;
+    let state = core.launched().ok_or(Error::InvalidState {
+        expected: "Launched",
+    })?;
 
     let mut connection = connect_s2api(addr).await?;
 
     // create game request
-    let mut create_game = protocol::RequestCreateGame::default();
-    create_game.player_setup = players;
-
-    create_game.map = Some(map.to_map());
-    create_game.realtime = Some(realtime);
-
-    let state = state
-        .create_game(&mut connection, create_game)
-        .await?
-        .ok_or(io::Error::from(io::ErrorKind::Interrupted))?;
-
-    let mut join_game = protocol::RequestJoinGame::default();
-    join_game.participation =
-        participant_race.map(protocol::request_join_game::Participation::Race);
-    join_game.options = Some(protocol::InterfaceOptions {
-        raw: Some(true),
+    let create_game = protocol::RequestCreateGame {
+        player_setup: players,
+        map: Some(map.to_map()),
+        realtime: Some(realtime),
         ..Default::default()
-    });
-    let state = state.join_game(&mut connection, join_game).await?.unwrap();
+    };
+
+    let state = state.create_game(&mut connection, create_game).await?;
+
+    let join_game = join_request(participant_race);
+    let state = state.join_game(&mut connection, join_game).await?;
 
     Ok((state, connection))
 }
+
+/// Start the replay at `path` observed from the perspective of `observed_player_id`.
+///
+/// Every player of a replay can be observed by starting it once per id listed in the replay
+/// info, see [`Launched::replay_info`](state_machine::Launched::replay_info).
+pub async fn start_replay<'core>(
+    core: &'core mut Core,
+    addr: impl ToSocketAddrs,
+    path: impl AsRef<std::path::Path>,
+    observed_player_id: u32,
+) -> Result<(InReplay<'core>, Connection)> {
+    let state = core.launched().ok_or(Error::InvalidState {
+        expected: "Launched",
+    })?;
+
+    let mut connection = connect_s2api(addr).await?;
+
+    let start_replay = protocol::RequestStartReplay {
+        replay: Some(protocol::request_start_replay::Replay::ReplayPath(
+            path.as_ref().to_string_lossy().into(),
+        )),
+        observed_player_id: Some(observed_player_id as i32),
+        options: Some(protocol::InterfaceOptions {
+            raw: Some(true),
+            score: Some(true),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let state = state.join_replay(&mut connection, start_replay).await?;
+
+    Ok((state, connection))
+}
+
+/// Join request with raw observations for a participant playing `race`
+pub(crate) fn join_request(race: Option<i32>) -> protocol::RequestJoinGame {
+    protocol::RequestJoinGame {
+        participation: race.map(protocol::request_join_game::Participation::Race),
+        options: Some(protocol::InterfaceOptions {
+            raw: Some(true),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,207 @@
+//! Games between several rsc2 agents, each one connected to its own SC2 instance.
+//!
+//! The host instance creates the game, then every participant joins with the same [`GamePorts`]
+//! so the instances can reach each other.
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+
+use rsc2_pb::protocol;
+
+use crate::{
+    Agent, Connection, Core, Error, InGame, Result, StepMode, agent, connect_s2api,
+    definitions::ToMapRef, join_request, launcher::Instance,
+};
+
+/// Ports shared by the instances of a multiplayer game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GamePorts {
+    pub shared: i32,
+    pub server: protocol::PortSet,
+    pub clients: Vec<protocol::PortSet>,
+}
+
+fn port_set(game_port: i32, base_port: i32) -> protocol::PortSet {
+    protocol::PortSet {
+        game_port: Some(game_port),
+        base_port: Some(base_port),
+    }
+}
+
+impl GamePorts {
+    /// Consecutive ports after `start`: the shared port, the server ports then two ports per
+    /// client.
+    pub fn contiguous(start: i32, clients: usize) -> Self {
+        Self {
+            shared: start + 1,
+            server: port_set(start + 2, start + 3),
+            clients: (0..clients as i32)
+                .map(|i| port_set(start + 4 + 2 * i, start + 5 + 2 * i))
+                .collect(),
+        }
+    }
+
+    /// Ports the OS reports as unused on localhost
+    pub fn unused(clients: usize) -> io::Result<Self> {
+        // listeners are kept alive until every port is picked so none is handed out twice
+        let listeners = (0..3 + 2 * clients)
+            .map(|_| TcpListener::bind((Ipv4Addr::LOCALHOST, 0)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let ports = listeners
+            .iter()
+            .map(|listener| listener.local_addr().map(|addr| i32::from(addr.port())))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self {
+            shared: ports[0],
+            server: port_set(ports[1], ports[2]),
+            clients: ports[3..]
+                .chunks(2)
+                .map(|ports| port_set(ports[0], ports[1]))
+                .collect(),
+        })
+    }
+
+    /// Sets the ports of a join request
+    pub fn apply(&self, join: &mut protocol::RequestJoinGame) {
+        join.shared_port = Some(self.shared);
+        join.server_ports = Some(self.server);
+        join.client_ports = self.clients.clone();
+    }
+}
+
+/// An agent along with the instance it plays on
+pub struct Participant<'a, A> {
+    pub core: &'a mut Core,
+    pub addr: SocketAddr,
+    pub player: protocol::PlayerSetup,
+    pub agent: &'a mut A,
+}
+
+impl<'a, A: Agent> Participant<'a, A> {
+    pub fn new(
+        core: &'a mut Core,
+        addr: SocketAddr,
+        player: impl Into<protocol::PlayerSetup>,
+        agent: &'a mut A,
+    ) -> Self {
+        Self {
+            core,
+            addr,
+            player: player.into(),
+            agent,
+        }
+    }
+    pub fn from_instance(
+        instance: &'a mut Instance,
+        player: impl Into<protocol::PlayerSetup>,
+        agent: &'a mut A,
+    ) -> Self {
+        let addr = instance.addr();
+        Self::new(instance.core(), addr, player, agent)
+    }
+}
+
+pub(crate) fn participant_join(
+    player: &protocol::PlayerSetup,
+    ports: &GamePorts,
+) -> protocol::RequestJoinGame {
+    let mut join = join_request(player.race);
+    join.player_name = player.player_name.clone();
+    ports.apply(&mut join);
+    join
+}
+
+/// Creates a two player game on the `host` instance and joins both instances concurrently.
+///
+/// Both cores must be launched, `realtime` applies to the whole game.
+pub async fn create_game<'h, 'g>(
+    (host, host_addr, host_player): (&'h mut Core, SocketAddr, &protocol::PlayerSetup),
+    (guest, guest_addr, guest_player): (&'g mut Core, SocketAddr, &protocol::PlayerSetup),
+    map: impl ToMapRef,
+    realtime: bool,
+    ports: &GamePorts,
+) -> Result<((InGame<'h>, Connection), (InGame<'g>, Connection))> {
+    let launched = || Error::InvalidState {
+        expected: "Launched",
+    };
+    let host = host.launched().ok_or_else(launched)?;
+    let guest = guest.launched().ok_or_else(launched)?;
+
+    let (mut host_connection, mut guest_connection) =
+        futures::try_join!(connect_s2api(host_addr), connect_s2api(guest_addr))?;
+
+    let create_game = protocol::RequestCreateGame {
+        player_setup: vec![host_player.clone(), guest_player.clone()],
+        map: Some(map.to_map()),
+        realtime: Some(realtime),
+        ..Default::default()
+    };
+    let host = host.create_game(&mut host_connection, create_game).await?;
+
+    // the host join only completes once every participant joined
+    let (host, guest) = futures::try_join!(
+        host.join_game(&mut host_connection, participant_join(host_player, ports)),
+        guest.join_game(&mut guest_connection, participant_join(guest_player, ports)),
+    )?;
+    Ok(((host, host_connection), (guest, guest_connection)))
+}
+
+/// Plays `host` against `guest` until the game ends, returns the results seen by each of them.
+pub async fn run_game<A: Agent, B: Agent>(
+    host: Participant<'_, A>,
+    guest: Participant<'_, B>,
+    map: impl ToMapRef,
+    mode: StepMode,
+    ports: &GamePorts,
+) -> Result<(Vec<protocol::PlayerResult>, Vec<protocol::PlayerResult>)> {
+    let ((host_state, mut host_connection), (guest_state, mut guest_connection)) = create_game(
+        (host.core, host.addr, &host.player),
+        (guest.core, guest.addr, &guest.player),
+        map,
+        mode.is_realtime(),
+        ports,
+    )
+    .await?;
+
+    futures::try_join!(
+        agent::play(host_state, &mut host_connection, mode, host.agent),
+        agent::play(guest_state, &mut guest_connection, mode, guest.agent),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contiguous_ports() {
+        let ports = GamePorts::contiguous(5000, 2);
+        let mut join = protocol::RequestJoinGame::default();
+        ports.apply(&mut join);
+
+        assert_eq!(join.shared_port, Some(5001));
+        assert_eq!(join.server_ports, Some(port_set(5002, 5003)));
+        assert_eq!(
+            join.client_ports,
+            vec![port_set(5004, 5005), port_set(5006, 5007)]
+        );
+    }
+
+    #[test]
+    fn test_unused_ports_are_distinct() {
+        let ports = GamePorts::unused(1).unwrap();
+        let mut all = vec![
+            ports.shared,
+            ports.server.game_port(),
+            ports.server.base_port(),
+        ];
+        all.extend(
+            ports
+                .clients
+                .iter()
+                .flat_map(|set| [set.game_port(), set.base_port()]),
+        );
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), 5);
+    }
+}
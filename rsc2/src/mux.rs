@@ -0,0 +1,356 @@
+//! Request multiplexing over a single api connection.
+//!
+//! Every request gets a fresh id and responses are routed back to their caller by matching
+//! `Response.id`, several requests can therefore be in flight at the same time.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU32, Ordering},
+};
+use std::task::{Context, Poll};
+
+use futures::channel::{mpsc, oneshot};
+use futures::{sink::Sink, stream::Stream};
+use rsc2_pb::protocol::{self, request, response};
+
+use crate::{Error, Result};
+
+macro_rules! variant_names {
+    {$($variant:ident),+} => {
+        fn request_variant(request: &request::Request) -> &'static str {
+            match request {
+                $(request::Request::$variant(_) => stringify!($variant),)+
+            }
+        }
+        fn response_variant(response: &response::Response) -> &'static str {
+            match response {
+                $(response::Response::$variant(_) => stringify!($variant),)+
+            }
+        }
+    };
+}
+
+variant_names! {
+    CreateGame, JoinGame, RestartGame, StartReplay, LeaveGame, QuickSave, QuickLoad, Quit,
+    GameInfo, Observation, Action, ObsAction, Step, Data, Query, SaveReplay, MapCommand,
+    ReplayInfo, AvailableMaps, SaveMap, Ping, Debug
+}
+
+type Reply = oneshot::Sender<Result<protocol::Response>>;
+
+struct Call {
+    request: protocol::Request,
+    reply: Reply,
+}
+
+struct Pending {
+    variant: Option<&'static str>,
+    reply: Reply,
+}
+
+/// Splits a transport in a cloneable request handle and the [`Driver`] owning the transport.
+///
+/// The driver must be polled (spawned or joined) for requests to make progress.
+pub fn multiplex<T>(transport: T) -> (Multiplexer, Driver<T>) {
+    let (calls, receiver) = mpsc::unbounded();
+    let mux = Multiplexer {
+        calls,
+        next_id: Arc::new(AtomicU32::new(1)),
+    };
+    let driver = Driver {
+        transport: Some(transport),
+        calls: receiver,
+        pending: BTreeMap::new(),
+        closed: false,
+    };
+    (mux, driver)
+}
+
+/// Handle issuing requests to a [`Driver`]
+#[derive(Clone)]
+pub struct Multiplexer {
+    calls: mpsc::UnboundedSender<Call>,
+    next_id: Arc<AtomicU32>,
+}
+
+impl Multiplexer {
+    /// Queues `request` under a fresh id, the request is sent even if the returned future is
+    /// never polled.
+    ///
+    /// Resolves with the response carrying the same id, an error is returned if the response
+    /// answers another kind of request.
+    pub fn call(
+        &self,
+        mut request: protocol::Request,
+    ) -> impl Future<Output = Result<protocol::Response>> + 'static {
+        request.id = Some(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let (reply, response) = oneshot::channel();
+        let queued = self.calls.unbounded_send(Call { request, reply }).is_ok();
+        async move {
+            if !queued {
+                return Err(Error::Closed);
+            }
+            response.await.unwrap_or(Err(Error::Closed))
+        }
+    }
+}
+
+impl fmt::Debug for Multiplexer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Multiplexer")
+            .field("next_id", &self.next_id)
+            .finish()
+    }
+}
+
+/// Drives the transport: sends queued requests and routes responses back by id.
+///
+/// Resolves with the transport once every [`Multiplexer`] handle is dropped and all pending
+/// requests are answered, or when the transport stream ends. An orphaned response (an id that
+/// was never sent) stops the driver with [`Error::Orphaned`].
+#[must_use = "requests make no progress unless the driver is polled"]
+pub struct Driver<T> {
+    transport: Option<T>,
+    calls: mpsc::UnboundedReceiver<Call>,
+    pending: BTreeMap<u32, Pending>,
+    closed: bool,
+}
+
+impl<T> Driver<T> {
+    /// Number of requests waiting for their response
+    pub fn in_flight(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Fails every pending request with `cause`, or [`Error::Disconnected`] wrapping it
+    fn fail_pending(&mut self, cause: &Error) {
+        for (_, pending) in std::mem::take(&mut self.pending) {
+            let error = match cause {
+                Error::Closed => Error::Closed,
+                cause => Error::Disconnected(cause.to_string()),
+            };
+            let _ = pending.reply.send(Err(error));
+        }
+    }
+}
+
+fn dispatch(pending: &mut BTreeMap<u32, Pending>, response: protocol::Response) -> Result<()> {
+    let id = response.id();
+    let Some(Pending { variant, reply }) = pending.remove(&id) else {
+        return Err(Error::Orphaned { id });
+    };
+    let answered = response.response.as_ref().map(response_variant);
+    let response = match (variant, answered) {
+        (Some(expected), Some(answered)) if expected != answered => {
+            warn!("response id: {} | got {} response", id, answered);
+            Err(Error::Unexpected { id, expected })
+        }
+        _ => Ok(response),
+    };
+    // the caller may have dropped its future, the response is discarded
+    let _ = reply.send(response);
+    Ok(())
+}
+
+impl<T> Driver<T>
+where
+    T: Stream<Item = io::Result<protocol::Response>>
+        + Sink<protocol::Request, Error = io::Error>
+        + Unpin,
+{
+    fn poll_transport(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let transport = self
+            .transport
+            .as_mut()
+            .expect("driver polled after completion");
+
+        while !self.closed {
+            if Pin::new(&mut *transport).poll_ready(cx)?.is_pending() {
+                break;
+            }
+            match Pin::new(&mut self.calls).poll_next(cx) {
+                Poll::Ready(Some(Call { request, reply })) => {
+                    let id = request.id();
+                    let variant = request.request.as_ref().map(request_variant);
+                    Pin::new(&mut *transport).start_send(request)?;
+                    self.pending.insert(id, Pending { variant, reply });
+                }
+                Poll::Ready(None) => self.closed = true,
+                Poll::Pending => break,
+            }
+        }
+        if let Poll::Ready(Err(e)) = Pin::new(&mut *transport).poll_flush(cx) {
+            return Poll::Ready(Err(e.into()));
+        }
+
+        loop {
+            match Pin::new(&mut *transport).poll_next(cx) {
+                Poll::Ready(Some(Ok(response))) => dispatch(&mut self.pending, response)?,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(e.into())),
+                Poll::Ready(None) => {
+                    self.fail_pending(&Error::Closed);
+                    return Poll::Ready(Ok(()));
+                }
+                Poll::Pending => break,
+            }
+        }
+
+        if self.closed && self.pending.is_empty() {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Future for Driver<T>
+where
+    T: Stream<Item = io::Result<protocol::Response>>
+        + Sink<protocol::Request, Error = io::Error>
+        + Unpin,
+{
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        match this.poll_transport(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(this
+                .transport
+                .take()
+                .expect("driver polled after completion"))),
+            Poll::Ready(Err(e)) => {
+                this.fail_pending(&e);
+                this.transport = None;
+                Poll::Ready(Err(e))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Driver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Driver")
+            .field("in_flight", &self.pending.keys().collect::<Vec<_>>())
+            .field("closed", &self.closed)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::StreamExt;
+
+    use crate::testing::{loopback, respond};
+
+    fn request(request: request::Request) -> protocol::Request {
+        protocol::Request {
+            id: None,
+            request: Some(request),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_out_of_order_responses() {
+        let (transport, (mut requests, responses)) = loopback();
+        let (mux, driver) = multiplex(transport);
+
+        let query = mux.call(request(request::Request::Query(Default::default())));
+        let observation = mux.call(request(request::Request::Observation(Default::default())));
+        drop(mux);
+
+        let server = async move {
+            let query = requests.next().await.unwrap();
+            let observation = requests.next().await.unwrap();
+            responses
+                .unbounded_send(respond(
+                    observation.id(),
+                    response::Response::Observation(Default::default()),
+                ))
+                .unwrap();
+            responses
+                .unbounded_send(respond(
+                    query.id(),
+                    response::Response::Query(Default::default()),
+                ))
+                .unwrap();
+            responses
+        };
+
+        let (query, observation, driver, _server) =
+            futures::join!(query, observation, driver, server);
+        assert!(matches!(
+            query.unwrap().response,
+            Some(response::Response::Query(_))
+        ));
+        assert!(matches!(
+            observation.unwrap().response,
+            Some(response::Response::Observation(_))
+        ));
+        assert!(driver.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_and_orphaned() {
+        let (transport, (mut requests, responses)) = loopback();
+        let (mux, driver) = multiplex(transport);
+
+        let ping = mux.call(request(request::Request::Ping(Default::default())));
+        let step = mux.call(request(request::Request::Step(Default::default())));
+
+        let server = async move {
+            let ping = requests.next().await.unwrap();
+            responses
+                .unbounded_send(respond(
+                    ping.id(),
+                    response::Response::Step(Default::default()),
+                ))
+                .unwrap();
+            responses
+                .unbounded_send(respond(1_000, response::Response::Step(Default::default())))
+                .unwrap();
+            responses
+        };
+
+        let (ping, step, driver, _server) = futures::join!(ping, step, driver, server);
+        assert!(matches!(
+            ping,
+            Err(Error::Unexpected {
+                expected: "Ping",
+                ..
+            })
+        ));
+        assert!(matches!(step, Err(Error::Disconnected(_))));
+        assert!(matches!(driver, Err(Error::Orphaned { id: 1_000 })));
+        drop(mux);
+    }
+
+    #[tokio::test]
+    async fn test_transport_error() {
+        let (transport, (mut requests, responses)) = loopback();
+        let (mux, driver) = multiplex(transport);
+
+        let ping = mux.call(request(request::Request::Ping(Default::default())));
+        let server = async move {
+            requests.next().await.unwrap();
+            responses
+                .unbounded_send(Err(io::ErrorKind::ConnectionReset.into()))
+                .unwrap();
+            responses
+        };
+
+        let (ping, driver, _server) = futures::join!(ping, driver, server);
+        assert!(matches!(ping, Err(Error::Disconnected(_))));
+        assert!(matches!(
+            driver,
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::ConnectionReset
+        ));
+        drop(mux);
+    }
+}
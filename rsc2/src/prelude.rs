@@ -2,6 +2,13 @@ pub use rsc2_pb::protocol::Difficulty;
 pub use rsc2_pb::protocol::Race;
 
 pub use crate::Connection;
+pub use crate::action::{ActionResults, Actions, Command, Target};
+pub use crate::agent::{Agent, run_game};
 pub use crate::definitions::Player;
+pub use crate::geometry::{Point2, Point3};
+pub use crate::ids::{AbilityId, UnitTypeId, UpgradeId};
+pub use crate::launcher::LaunchOptions;
 pub use crate::state_machine::Core;
-pub use crate::{connect_s2api, create_game};
+pub use crate::stepper::StepMode;
+pub use crate::unit::{Unit, Units};
+pub use crate::{connect_s2api, create_game, start_replay};
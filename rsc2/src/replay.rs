@@ -0,0 +1,182 @@
+//! Replay playback from the perspective of an observer.
+use rsc2_pb::protocol::{self, Status, observer_action::Action};
+
+use crate::{
+    Connection, Result,
+    state_machine::{Ended, InReplay, server_call_status},
+};
+
+/// Steps through a replay, yielding the observations of the observed player
+pub struct ReplayObserver<'sm, 'b> {
+    state: InReplay<'sm>,
+    framed: &'b mut Connection,
+    count: u32,
+    game_loop: u32,
+    /// The last step ended the replay, the final observation is still to be requested
+    stepped_to_end: bool,
+    ended: bool,
+    results: Vec<protocol::PlayerResult>,
+}
+
+impl<'sm, 'b> ReplayObserver<'sm, 'b> {
+    pub(crate) fn new(state: InReplay<'sm>, framed: &'b mut Connection, count: u32) -> Self {
+        Self {
+            state,
+            framed,
+            count,
+            game_loop: 0,
+            stepped_to_end: false,
+            ended: false,
+            results: Vec::new(),
+        }
+    }
+    /// Game loop of the last observation or step
+    pub fn game_loop(&self) -> u32 {
+        self.game_loop
+    }
+    pub fn is_ended(&self) -> bool {
+        self.ended
+    }
+
+    /// Observes the current game loop then steps the replay, `None` once the replay has ended.
+    ///
+    /// The last observation carries the results of the players.
+    pub async fn next_observation(&mut self) -> Result<Option<protocol::ResponseObservation>> {
+        if self.ended {
+            return Ok(None);
+        }
+        let (_, status, observation) =
+            server_call_status(self.framed, protocol::RequestObservation::default()).await?;
+        if let Some(game_loop) = observation.observation.as_ref().and_then(|o| o.game_loop) {
+            self.game_loop = game_loop;
+        }
+        if !observation.player_result.is_empty() {
+            self.results.clone_from(&observation.player_result);
+        }
+        if status == Status::Ended || self.stepped_to_end {
+            self.ended = true;
+            return Ok(Some(observation));
+        }
+
+        let request = protocol::RequestStep {
+            count: Some(self.count),
+        };
+        let (_, status, step) = server_call_status(self.framed, request).await?;
+        self.game_loop = step.simulation_loop.unwrap_or(self.game_loop + self.count);
+        // the results are only carried by the observation following the end of the replay
+        self.stepped_to_end = status == Status::Ended;
+        Ok(Some(observation))
+    }
+
+    pub async fn replay_info(
+        &mut self,
+        data: protocol::RequestReplayInfo,
+    ) -> Result<protocol::ResponseReplayInfo> {
+        self.state.replay_info(self.framed, data).await
+    }
+    pub async fn observer_action(&mut self, actions: Vec<protocol::ObserverAction>) -> Result<()> {
+        self.state.observer_action(self.framed, actions).await
+    }
+    async fn observer(&mut self, action: Action) -> Result<()> {
+        let action = protocol::ObserverAction {
+            action: Some(action),
+        };
+        self.observer_action(vec![action]).await
+    }
+    /// Shows the interface of `player_id`, `0` shows every player
+    pub async fn player_perspective(&mut self, player_id: u32) -> Result<()> {
+        self.observer(Action::PlayerPerspective(
+            protocol::ActionObserverPlayerPerspective {
+                player_id: Some(player_id),
+            },
+        ))
+        .await
+    }
+    pub async fn camera_move(&mut self, world_pos: protocol::Point2D, distance: f32) -> Result<()> {
+        self.observer(Action::CameraMove(protocol::ActionObserverCameraMove {
+            world_pos: Some(world_pos),
+            distance: Some(distance),
+        }))
+        .await
+    }
+    pub async fn camera_follow_player(&mut self, player_id: u32) -> Result<()> {
+        self.observer(Action::CameraFollowPlayer(
+            protocol::ActionObserverCameraFollowPlayer {
+                player_id: Some(player_id),
+            },
+        ))
+        .await
+    }
+    pub async fn camera_follow_units(&mut self, unit_tags: Vec<u64>) -> Result<()> {
+        self.observer(Action::CameraFollowUnits(
+            protocol::ActionObserverCameraFollowUnits { unit_tags },
+        ))
+        .await
+    }
+
+    /// Ends the replay, whether or not it was played to the end
+    pub fn into_ended(self) -> Ended<'sm> {
+        debug!("replay stopped at game loop {}", self.game_loop);
+        self.state.end(self.results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rsc2_pb::protocol::{request::Request, response::Response};
+
+    use super::*;
+    use crate::{
+        Core, start_replay,
+        testing::{fake_api, reply},
+    };
+
+    #[tokio::test]
+    async fn test_results_after_last_step() {
+        let mut ended = false;
+        let (addr, server) = fake_api(move |request| match &request.request {
+            Some(Request::StartReplay(_)) => {
+                reply(Status::InReplay, Response::StartReplay(Default::default()))
+            }
+            Some(Request::Observation(_)) if !ended => {
+                reply(Status::InReplay, Response::Observation(Default::default()))
+            }
+            Some(Request::Observation(_)) => reply(
+                Status::Ended,
+                Response::Observation(protocol::ResponseObservation {
+                    player_result: vec![protocol::PlayerResult {
+                        player_id: Some(1),
+                        result: Some(protocol::Result::Victory as i32),
+                    }],
+                    ..Default::default()
+                }),
+            ),
+            Some(Request::Step(_)) => {
+                ended = true;
+                let step = protocol::ResponseStep {
+                    simulation_loop: Some(8),
+                };
+                reply(Status::Ended, Response::Step(step))
+            }
+            other => panic!("unexpected request {other:?}"),
+        })
+        .await;
+
+        let mut core = Core::init();
+        let (state, mut connection) = start_replay(&mut core, addr, "replay", 1).await.unwrap();
+        let mut observer = state.observe(&mut connection, 8);
+        let mut observations = Vec::new();
+        while let Some(observation) = observer.next_observation().await.unwrap() {
+            observations.push(observation);
+        }
+        assert_eq!(observations.len(), 2);
+        assert_eq!(observations[1].player_result.len(), 1);
+        assert_eq!(observer.game_loop(), 8);
+        let ended = observer.into_ended();
+        assert_eq!(ended.results(), observations[1].player_result);
+        drop(connection);
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 4);
+    }
+}
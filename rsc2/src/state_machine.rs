@@ -1,47 +1,45 @@
-use std::io;
+use crate::{
+    Connection, Error, Result,
+    client::{ApiRequest, typed_response},
+    ingame::InGameListener,
+    replay::ReplayObserver,
+};
 
-use crate::{Connection, ingame::InGameListener};
+use std::path::PathBuf;
 
 use futures::{sink::SinkExt, stream::StreamExt};
-use rsc2_pb::protocol::{self, Status, response::Response};
-
-macro_rules! server_call {
-    ($conn:ident, $req:ident, $resp:path) => {
-        async {
-            let _: &mut Connection = $conn;
-            $conn.send($req).await?;
-            let res = match $conn.next().await {
-                Some(Ok(res)) => res,
-                Some(Err(err)) => return Err(err),
-                None => return Ok(None),
-            };
+use rsc2_pb::protocol;
 
-            let status = res.status();
-            let id = res.id();
-            let $crate::protocol::Response {
-                response, error, ..
-            } = &res;
-
-            error
-                .iter()
-                .for_each(|err| warn!("response id: {} | err: {}", id, err));
-            if matches!(status, Status::Quit | Status::Unknown) {
-                info!("status: {:?}, interupting state machine", status);
-                return Result::<_, io::Error>::Ok(None);
-            }
-            Ok(match response {
-                Some($resp(response)) => {
-                    if response.error.is_some() {
-                        error!("response id: {} | err: {:?}", id, response.error());
-                        None
-                    } else {
-                        Some(res)
-                    }
-                }
-                _ => None,
-            })
-        }
+/// Sends `request` and waits for the next frame, which has to answer it.
+async fn server_call<R: ApiRequest>(
+    conn: &mut Connection,
+    request: R,
+) -> Result<(u32, R::Response)> {
+    server_call_status(conn, request)
+        .await
+        .map(|(id, _, response)| (id, response))
+}
+
+/// [`server_call`] that also returns the status the server reported with the response
+pub(crate) async fn server_call_status<R: ApiRequest>(
+    conn: &mut Connection,
+    request: R,
+) -> Result<(u32, protocol::Status, R::Response)> {
+    let request: protocol::Request = request.into();
+    conn.send(request).await?;
+    let sent = conn.codec().id();
+    let res = match conn.next().await {
+        Some(res) => res?,
+        None => return Err(Error::Closed),
     };
+    if res.id.is_some_and(|id| id != sent) {
+        return Err(Error::MismatchedId {
+            id: res.id(),
+            expected: sent,
+        });
+    }
+    let status = res.status();
+    typed_response::<R>(res).map(|(id, response)| (id, status, response))
 }
 
 macro_rules! impl_from {
@@ -55,15 +53,20 @@ macro_rules! impl_from {
     };
 }
 
+#[derive(Debug)]
 pub enum Core {
     Launched {},
     InitGame {},
     InGame {},
     InReplay {},
-    Ended {},
+    Ended {
+        results: Vec<protocol::PlayerResult>,
+    },
 }
 
 impl Core {
+    /// State of an instance that is already running and accepting connections, such as one
+    /// started by [`LaunchOptions`](crate::launcher::LaunchOptions) or by a ladder manager
     pub const fn init() -> Self {
         Self::Launched {}
     }
@@ -73,6 +76,13 @@ impl Core {
             _ => None,
         }
     }
+    /// Results of the last game, empty unless the game has ended
+    pub fn player_results(&self) -> &[protocol::PlayerResult] {
+        match self {
+            Self::Ended { results } => results,
+            _ => &[],
+        }
+    }
     pub fn replace(&mut self, new: Self) -> Self {
         let (a, b) = (&self, &new);
         debug_assert!(
@@ -127,45 +137,93 @@ impl_from!(Launched -> InReplay);
 pub struct Ended<'a>(&'a mut Core);
 impl_from!(InGame -> Ended);
 impl_from!(InReplay -> Ended);
+impl_from!(Ended -> Launched);
+impl_from!(Ended -> InGame);
 
 impl<'a> Launched<'a> {
     pub fn core(&mut self) -> &mut Core {
         self.0
     }
+    pub async fn replay_info(
+        &mut self,
+        framed: &mut Connection,
+        data: protocol::RequestReplayInfo,
+    ) -> Result<protocol::ResponseReplayInfo> {
+        replay_info(framed, data).await
+    }
     pub async fn create_game(
         self,
         framed: &mut Connection,
         data: protocol::RequestCreateGame,
-    ) -> io::Result<Option<InitGame<'a>>> {
-        let resp = server_call!(framed, data, Response::CreateGame).await?;
-        Ok(resp.map(|_| {
-            self.0.replace(Core::InitGame {});
-            InitGame::from(self)
-        }))
+    ) -> Result<InitGame<'a>> {
+        let (id, resp) = server_call(framed, data).await?;
+        if resp.error.is_some() {
+            return Err(Error::CreateGame {
+                id,
+                error: resp.error(),
+                details: resp.error_details.unwrap_or_default(),
+            });
+        }
+        self.0.replace(Core::InitGame {});
+        Ok(InitGame::from(self))
     }
     pub async fn join_game(
         self,
         framed: &mut Connection,
         data: protocol::RequestJoinGame,
-    ) -> io::Result<Option<InGame<'a>>> {
-        let resp = server_call!(framed, data, Response::JoinGame).await?;
-        Ok(resp.map(|_| {
-            self.0.replace(Core::InGame {});
-            InGame::from(self)
-        }))
+    ) -> Result<InGame<'a>> {
+        join_game(framed, data).await?;
+        self.0.replace(Core::InGame {});
+        Ok(InGame::from(self))
     }
     pub async fn join_replay(
         self,
         framed: &mut Connection,
         data: protocol::RequestStartReplay,
-    ) -> io::Result<Option<InReplay<'a>>> {
-        let resp = server_call!(framed, data, Response::StartReplay).await?;
-        Ok(resp.map(|_| {
-            self.0.replace(Core::InReplay {});
-            InReplay::from(self)
-        }))
+    ) -> Result<InReplay<'a>> {
+        let (id, resp) = server_call(framed, data).await?;
+        if resp.error.is_some() {
+            return Err(Error::StartReplay {
+                id,
+                error: resp.error(),
+                details: resp.error_details.unwrap_or_default(),
+            });
+        }
+        self.0.replace(Core::InReplay {});
+        Ok(InReplay::from(self))
+    }
+}
+
+async fn replay_info(
+    framed: &mut Connection,
+    data: protocol::RequestReplayInfo,
+) -> Result<protocol::ResponseReplayInfo> {
+    let (id, resp) = server_call(framed, data).await?;
+    if resp.error.is_some() {
+        return Err(Error::ReplayInfo {
+            id,
+            error: resp.error(),
+            details: resp.error_details.unwrap_or_default(),
+        });
     }
+    Ok(resp)
 }
+
+async fn join_game(
+    framed: &mut Connection,
+    data: protocol::RequestJoinGame,
+) -> Result<protocol::ResponseJoinGame> {
+    let (id, resp) = server_call(framed, data).await?;
+    if resp.error.is_some() {
+        return Err(Error::JoinGame {
+            id,
+            error: resp.error(),
+            details: resp.error_details.unwrap_or_default(),
+        });
+    }
+    Ok(resp)
+}
+
 impl<'a> InitGame<'a> {
     pub fn core(&mut self) -> &mut Core {
         self.0
@@ -174,12 +232,10 @@ impl<'a> InitGame<'a> {
         self,
         framed: &mut Connection,
         data: protocol::RequestJoinGame,
-    ) -> io::Result<Option<InGame<'a>>> {
-        let resp = server_call!(framed, data, Response::JoinGame).await?;
-        Ok(resp.map(|_| {
-            self.0.replace(Core::InGame {});
-            InGame::from(self)
-        }))
+    ) -> Result<InGame<'a>> {
+        join_game(framed, data).await?;
+        self.0.replace(Core::InGame {});
+        Ok(InGame::from(self))
     }
 }
 
@@ -192,3 +248,245 @@ impl<'a> InGame<'a> {
         InGameListener::new(self, framed)
     }
 }
+
+impl<'a> InReplay<'a> {
+    pub fn core(&mut self) -> &mut Core {
+        self.0
+    }
+    pub async fn replay_info(
+        &mut self,
+        framed: &mut Connection,
+        data: protocol::RequestReplayInfo,
+    ) -> Result<protocol::ResponseReplayInfo> {
+        replay_info(framed, data).await
+    }
+    /// Controls the observer interface and camera
+    pub async fn observer_action(
+        &mut self,
+        framed: &mut Connection,
+        actions: Vec<protocol::ObserverAction>,
+    ) -> Result<()> {
+        server_call(framed, protocol::RequestObserverAction { actions }).await?;
+        Ok(())
+    }
+    /// Observes the replay every `count` game loops, see [`ReplayObserver`]
+    pub fn observe(self, framed: &mut Connection, count: u32) -> ReplayObserver<'a, '_> {
+        ReplayObserver::new(self, framed, count)
+    }
+    /// Stops observing with the `results` seen so far, the replay can't be resumed
+    pub fn end(self, results: Vec<protocol::PlayerResult>) -> Ended<'a> {
+        self.0.replace(Core::Ended { results });
+        Ended::from(self)
+    }
+}
+
+impl<'a> Ended<'a> {
+    pub fn core(&mut self) -> &mut Core {
+        self.0
+    }
+    /// Results reported by the final observation of the game, also when the game was ended by
+    /// a step or an action
+    pub fn results(&self) -> &[protocol::PlayerResult] {
+        self.0.player_results()
+    }
+    /// Replay of the game that just ended
+    pub async fn save_replay(&mut self, framed: &mut Connection) -> Result<Vec<u8>> {
+        let (_, resp) = server_call(framed, protocol::RequestSaveReplay {}).await?;
+        Ok(resp.data.unwrap_or_default())
+    }
+    /// Writes the replay of the game to `path`, which is returned
+    pub async fn save_replay_to(
+        &mut self,
+        framed: &mut Connection,
+        path: impl Into<PathBuf>,
+    ) -> Result<PathBuf> {
+        let path = path.into();
+        let replay = self.save_replay(framed).await?;
+        tokio::fs::write(&path, replay).await?;
+        Ok(path)
+    }
+    /// Restarts the same game, only available in single player games
+    pub async fn restart_game(self, framed: &mut Connection) -> Result<InGame<'a>> {
+        let (id, resp) = server_call(framed, protocol::RequestRestartGame {}).await?;
+        if resp.error.is_some() {
+            return Err(Error::RestartGame {
+                id,
+                error: resp.error(),
+                need_hard_reset: resp.need_hard_reset(),
+                details: resp.error_details.unwrap_or_default(),
+            });
+        }
+        self.0.replace(Core::InGame {});
+        Ok(InGame::from(self))
+    }
+    /// Leaves the game, the instance can then create or join another one
+    pub async fn leave(self, framed: &mut Connection) -> Result<Launched<'a>> {
+        server_call(framed, protocol::RequestLeaveGame {}).await?;
+        self.0.replace(Core::Launched {});
+        Ok(Launched::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rsc2_pb::protocol::{Status, request::Request, response::Response};
+
+    use super::*;
+    use crate::{
+        connect_s2api,
+        testing::{fake_api, fake_step_game, reply},
+    };
+
+    #[tokio::test]
+    async fn test_create_game_error() {
+        let (addr, _server) = fake_api(|_| {
+            let mut create_game = protocol::ResponseCreateGame {
+                error_details: Some("map not found".into()),
+                ..Default::default()
+            };
+            create_game.set_error(protocol::response_create_game::Error::InvalidMapPath);
+            reply(Status::Launched, Response::CreateGame(create_game))
+        })
+        .await;
+        let mut connection = connect_s2api(addr).await.unwrap();
+
+        let mut core = Core::init();
+        let state = core.launched().unwrap();
+        let error = state
+            .create_game(&mut connection, Default::default())
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            Error::CreateGame {
+                error: protocol::response_create_game::Error::InvalidMapPath,
+                ref details,
+                ..
+            } if details == "map not found"
+        ));
+        assert!(core.launched().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_join_game_error() {
+        let (addr, _server) = fake_api(|request| match &request.request {
+            Some(Request::JoinGame(_)) => {
+                let mut join_game = protocol::ResponseJoinGame::default();
+                join_game.set_error(protocol::response_join_game::Error::MissingParticipation);
+                reply(Status::Launched, Response::JoinGame(join_game))
+            }
+            other => panic!("unexpected request {other:?}"),
+        })
+        .await;
+        let mut connection = connect_s2api(addr).await.unwrap();
+
+        let mut core = Core::init();
+        let state = core.launched().unwrap();
+        let error = state
+            .join_game(&mut connection, Default::default())
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            Error::JoinGame {
+                error: protocol::response_join_game::Error::MissingParticipation,
+                ..
+            }
+        ));
+        assert!(core.launched().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_results_of_step_ended_game() {
+        let (addr, _server) = fake_api(fake_step_game(2)).await;
+        let mut connection = connect_s2api(addr).await.unwrap();
+
+        let mut core = Core::init();
+        let state = core.launched().unwrap();
+        let state = state
+            .join_game(&mut connection, Default::default())
+            .await
+            .unwrap();
+        let mut listener = state.stream(&mut connection);
+        let step = protocol::RequestStep { count: Some(2) };
+        assert!(listener.call(step).await.unwrap().is_none());
+        let ended = listener.into_ended();
+        assert_eq!(
+            ended.results(),
+            [protocol::PlayerResult {
+                player_id: Some(1),
+                result: Some(protocol::Result::Victory as i32),
+            }]
+        );
+    }
+
+    /// Api answering the requests of an ended game
+    fn ended_api(request: &protocol::Request) -> protocol::Response {
+        match &request.request {
+            Some(Request::SaveReplay(_)) => {
+                let save_replay = protocol::ResponseSaveReplay {
+                    data: Some(b"replay".to_vec()),
+                };
+                reply(Status::Ended, Response::SaveReplay(save_replay))
+            }
+            Some(Request::RestartGame(_)) => {
+                reply(Status::InGame, Response::RestartGame(Default::default()))
+            }
+            Some(Request::LeaveGame(_)) => {
+                reply(Status::Launched, Response::LeaveGame(Default::default()))
+            }
+            other => panic!("unexpected request {other:?}"),
+        }
+    }
+
+    fn ended_core() -> Core {
+        Core::Ended {
+            results: vec![protocol::PlayerResult {
+                player_id: Some(1),
+                result: Some(protocol::Result::Victory as i32),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_replay_and_restart() {
+        let (addr, _server) = fake_api(ended_api).await;
+        let mut connection = connect_s2api(addr).await.unwrap();
+
+        let mut core = ended_core();
+        let mut state = Ended(&mut core);
+        assert_eq!(state.results().len(), 1);
+        let path = std::env::temp_dir().join(format!("rsc2-{}.SC2Replay", std::process::id()));
+        let saved = state.save_replay_to(&mut connection, &path).await;
+        let replay = std::fs::read(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(saved.unwrap(), path);
+        assert_eq!(replay.unwrap(), b"replay");
+
+        state.restart_game(&mut connection).await.unwrap();
+        assert!(matches!(core, Core::InGame {}));
+        assert!(core.player_results().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_leave() {
+        let (addr, server) = fake_api(ended_api).await;
+        let mut connection = connect_s2api(addr).await.unwrap();
+
+        let mut core = ended_core();
+        Ended(&mut core).leave(&mut connection).await.unwrap();
+        assert!(core.launched().is_some());
+
+        drop(connection);
+        let requests = server.await.unwrap();
+        assert!(matches!(
+            requests[..],
+            [protocol::Request {
+                request: Some(Request::LeaveGame(_)),
+                ..
+            }]
+        ));
+    }
+}
@@ -0,0 +1,250 @@
+//! Game loop driver alternating observations, agent actions and simulation steps.
+use rsc2_pb::protocol;
+
+use crate::{
+    InGameListener, Result,
+    action::{ActionResults, Actions},
+    state_machine::Ended,
+};
+
+/// How the game advances between two agent steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepMode {
+    /// The simulation waits for the agent and is advanced by `count` game loops with a
+    /// `RequestStep`, the game must be created with `realtime: false`.
+    Step(u32),
+    /// The simulation runs on its own, observations are requested every `count` game loops.
+    Realtime(u32),
+}
+
+impl StepMode {
+    pub fn is_realtime(&self) -> bool {
+        matches!(self, Self::Realtime(_))
+    }
+    /// Game loops between two observations
+    pub fn count(&self) -> u32 {
+        match *self {
+            Self::Step(count) | Self::Realtime(count) => count,
+        }
+    }
+}
+
+impl Default for StepMode {
+    fn default() -> Self {
+        Self::Step(1)
+    }
+}
+
+/// Drives an [`InGameListener`] until the server reports the game as ended
+pub struct Stepper<'sm, 'b> {
+    listener: InGameListener<'sm, 'b>,
+    mode: StepMode,
+    game_loop: u32,
+    results: ActionResults,
+}
+
+impl<'sm, 'b> Stepper<'sm, 'b> {
+    pub fn new(listener: InGameListener<'sm, 'b>, mode: StepMode) -> Self {
+        Self {
+            listener,
+            mode,
+            game_loop: 0,
+            results: ActionResults::default(),
+        }
+    }
+    pub fn mode(&self) -> StepMode {
+        self.mode
+    }
+    /// Game loop of the last observation or step
+    pub fn game_loop(&self) -> u32 {
+        self.game_loop
+    }
+    /// Listener used to issue requests outside of the step cycle
+    pub fn listener(&mut self) -> &mut InGameListener<'sm, 'b> {
+        &mut self.listener
+    }
+
+    /// Runs one observation, `on_step`, actions, step cycle, returns `false` once the game has
+    /// ended.
+    ///
+    /// `on_step` is given the results of the actions sent on the previous step.
+    pub async fn step<F>(&mut self, on_step: F) -> Result<bool>
+    where
+        F: AsyncFnOnce(&protocol::ResponseObservation, &ActionResults) -> Actions,
+    {
+        let request = protocol::RequestObservation {
+            game_loop: match self.mode {
+                StepMode::Realtime(count) if self.game_loop > 0 => Some(self.game_loop + count),
+                _ => None,
+            },
+            ..Default::default()
+        };
+        let Some(observation) = self.listener.call(request).await? else {
+            return Ok(false);
+        };
+        if let Some(game_loop) = observation.observation.as_ref().and_then(|o| o.game_loop) {
+            self.game_loop = game_loop;
+        }
+
+        let results = std::mem::take(&mut self.results);
+        let actions = on_step(&observation, &results).await;
+        if !actions.is_empty() {
+            let (commands, actions) = actions.into_request();
+            let Some(response) = self
+                .listener
+                .call(protocol::RequestAction { actions })
+                .await?
+            else {
+                return Ok(false);
+            };
+            self.results = ActionResults::new(commands, &response);
+            for failed in self.results.failed() {
+                debug!("game loop {}: {:?}", self.game_loop, failed);
+            }
+        }
+
+        if let StepMode::Step(count) = self.mode {
+            let request = protocol::RequestStep { count: Some(count) };
+            match self.listener.call(request).await? {
+                Some(step) => {
+                    self.game_loop = step.simulation_loop.unwrap_or(self.game_loop + count)
+                }
+                None => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Steps until the game ends
+    pub async fn run<F>(mut self, mut on_step: F) -> Result<Ended<'sm>>
+    where
+        F: AsyncFnMut(&protocol::ResponseObservation, &ActionResults) -> Actions,
+    {
+        while self.step(&mut on_step).await? {}
+        Ok(self.into_ended())
+    }
+
+    /// Hands back the ended state once [`step`](Self::step) returned `false`
+    pub fn into_ended(self) -> Ended<'sm> {
+        debug!("game ended at game loop {}", self.game_loop);
+        self.listener.into_ended()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rsc2_pb::protocol::request::Request;
+
+    use super::*;
+    use crate::{
+        Core,
+        action::{Command, Target},
+        connect_s2api,
+        testing::{fake_api, fake_game, fake_step_game},
+    };
+
+    fn names(requests: Vec<protocol::Request>) -> Vec<&'static str> {
+        requests
+            .into_iter()
+            .map(|request| match request.request {
+                Some(Request::JoinGame(_)) => "join",
+                Some(Request::Observation(_)) => "observation",
+                Some(Request::Action(_)) => "action",
+                Some(Request::Step(_)) => "step",
+                _ => "other",
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_step_cycle() {
+        let (addr, server) = fake_api(fake_game(4)).await;
+        let mut connection = connect_s2api(addr).await.unwrap();
+        let mut core = Core::init();
+        let state = core.launched().unwrap();
+        let state = state
+            .join_game(&mut connection, Default::default())
+            .await
+            .unwrap();
+
+        let mut stepper = Stepper::new(state.stream(&mut connection), StepMode::Step(2));
+        let mut steps = Vec::new();
+        while stepper
+            .step(async |observation, results| {
+                steps.push((observation.observation.clone(), results.commands().len()));
+                let mut actions = Actions::new();
+                actions.push(Command::new(1u32, [1], Target::None));
+                actions
+            })
+            .await
+            .unwrap()
+        {}
+        assert_eq!(stepper.game_loop(), 4);
+        let ended = stepper.into_ended();
+        assert_eq!(ended.results().len(), 1);
+        drop(connection);
+
+        // the results of the actions sent on a step are given to the next one
+        let game_loops: Vec<_> = steps
+            .iter()
+            .map(|(observation, results)| (observation.as_ref().unwrap().game_loop(), *results))
+            .collect();
+        assert_eq!(game_loops, [(0, 0), (2, 1)]);
+
+        assert_eq!(
+            names(server.await.unwrap()),
+            [
+                "join",
+                "observation",
+                "action",
+                "step",
+                "observation",
+                "action",
+                "step",
+                "observation"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_step_ended() {
+        let (addr, server) = fake_api(fake_step_game(4)).await;
+        let mut connection = connect_s2api(addr).await.unwrap();
+        let mut core = Core::init();
+        let state = core.launched().unwrap();
+        let state = state
+            .join_game(&mut connection, Default::default())
+            .await
+            .unwrap();
+
+        let mut stepper = Stepper::new(state.stream(&mut connection), StepMode::Step(2));
+        let mut steps = 0;
+        while stepper
+            .step(async |_, _| {
+                steps += 1;
+                Actions::new()
+            })
+            .await
+            .unwrap()
+        {}
+        assert_eq!(steps, 2);
+        assert!(stepper.listener().is_ended());
+        // the results are read from the observation following the step that ended the game
+        let ended = stepper.into_ended();
+        assert_eq!(ended.results().len(), 1);
+        assert_eq!(ended.results()[0].result(), protocol::Result::Victory);
+        drop(connection);
+
+        assert_eq!(
+            names(server.await.unwrap()),
+            [
+                "join",
+                "observation",
+                "step",
+                "observation",
+                "step",
+                "observation"
+            ]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Fake SC2 api answering the requests of a [`Connection`](crate::Connection) or of a
+//! [`Multiplexer`](crate::mux::Multiplexer) in tests, available to other crates with the
+//! `testing` feature.
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use prost::Message as _;
+use rsc2_pb::protocol::{self, Status, request::Request, response::Response};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use tokio_util::codec::Framed;
+use websocket_codec::{ClientRequest, Message, MessageCodec};
+
+/// Response with a status, the id is set to the one of the request it answers
+pub fn reply(status: Status, response: Response) -> protocol::Response {
+    let mut reply = protocol::Response {
+        response: Some(response),
+        ..Default::default()
+    };
+    reply.set_status(status);
+    reply
+}
+
+async fn handshake(stream: &mut TcpStream) {
+    let mut request = Vec::new();
+    while !request.ends_with(b"\r\n\r\n") {
+        request.push(stream.read_u8().await.expect("handshake request"));
+    }
+    let request = String::from_utf8(request).expect("utf8 handshake");
+    let headers: Vec<(&str, &str)> = request
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .collect();
+    let accept = ClientRequest::parse(|name| {
+        headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    })
+    .expect("websocket upgrade")
+    .ws_accept();
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {accept}\r\n\r\n"
+    );
+    stream.write_all(response.as_bytes()).await.unwrap();
+}
+
+/// Serves a single connection on localhost, `respond` answers every request until the client
+/// disconnects. The task resolves with the requests it received.
+pub async fn fake_api<F>(mut respond: F) -> (SocketAddr, JoinHandle<Vec<protocol::Request>>)
+where
+    F: FnMut(&protocol::Request) -> protocol::Response + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        handshake(&mut stream).await;
+        let mut framed = Framed::new(stream, MessageCodec::server());
+        let mut requests = Vec::new();
+        while let Some(Ok(message)) = framed.next().await {
+            if message.opcode().is_control() {
+                continue;
+            }
+            let request = protocol::Request::decode(message.into_data()).unwrap();
+            let mut response = respond(&request);
+            response.id = request.id;
+            let sent = framed.send(Message::binary(response.encode_to_vec())).await;
+            requests.push(request);
+            if sent.is_err() {
+                break;
+            }
+        }
+        requests
+    });
+    (addr, server)
+}
+
+/// Answers of a single player game against the computer that the player wins once the
+/// simulation reaches `end_at`, every action succeeds. The game is reported as ended by the
+/// first observation past `end_at`.
+pub fn fake_game(end_at: u32) -> impl FnMut(&protocol::Request) -> protocol::Response {
+    game(end_at, false)
+}
+
+/// [`fake_game`] reported as ended by the step reaching `end_at`, as SC2 does in step mode.
+/// The results are carried by the observation that follows.
+pub fn fake_step_game(end_at: u32) -> impl FnMut(&protocol::Request) -> protocol::Response {
+    game(end_at, true)
+}
+
+fn game(end_at: u32, ended_on_step: bool) -> impl FnMut(&protocol::Request) -> protocol::Response {
+    let mut game_loop = 0;
+    move |request| match &request.request {
+        Some(Request::JoinGame(_)) => reply(
+            Status::InGame,
+            Response::JoinGame(protocol::ResponseJoinGame {
+                player_id: Some(1),
+                ..Default::default()
+            }),
+        ),
+        Some(Request::GameInfo(_)) => reply(Status::InGame, Response::GameInfo(Default::default())),
+        Some(Request::Data(_)) => reply(Status::InGame, Response::Data(Default::default())),
+        Some(Request::Observation(_)) if game_loop >= end_at => reply(
+            Status::Ended,
+            Response::Observation(protocol::ResponseObservation {
+                player_result: vec![protocol::PlayerResult {
+                    player_id: Some(1),
+                    result: Some(protocol::Result::Victory as i32),
+                }],
+                ..Default::default()
+            }),
+        ),
+        Some(Request::Observation(_)) => reply(
+            Status::InGame,
+            Response::Observation(protocol::ResponseObservation {
+                observation: Some(protocol::Observation {
+                    game_loop: Some(game_loop),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        ),
+        Some(Request::Action(action)) => reply(
+            Status::InGame,
+            Response::Action(protocol::ResponseAction {
+                result: vec![protocol::ActionResult::Success as i32; action.actions.len()],
+            }),
+        ),
+        Some(Request::Step(step)) => {
+            game_loop += step.count();
+            let status = if ended_on_step && game_loop >= end_at {
+                Status::Ended
+            } else {
+                Status::InGame
+            };
+            reply(
+                status,
+                Response::Step(protocol::ResponseStep {
+                    simulation_loop: Some(game_loop),
+                }),
+            )
+        }
+        other => panic!("unexpected request {other:?}"),
+    }
+}
+
+/// In memory transport, requests are forwarded to the test which answers them
+#[derive(Debug)]
+pub struct Loopback {
+    requests: mpsc::UnboundedSender<protocol::Request>,
+    responses: mpsc::UnboundedReceiver<io::Result<protocol::Response>>,
+}
+
+impl Stream for Loopback {
+    type Item = io::Result<protocol::Response>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.responses.poll_next_unpin(cx)
+    }
+}
+
+impl Sink<protocol::Request> for Loopback {
+    type Error = io::Error;
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+    fn start_send(self: Pin<&mut Self>, item: protocol::Request) -> io::Result<()> {
+        self.requests
+            .unbounded_send(item)
+            .map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Ends of a [`Loopback`] held by the test, the requests sent and the responses to send back
+pub type Server = (
+    mpsc::UnboundedReceiver<protocol::Request>,
+    mpsc::UnboundedSender<io::Result<protocol::Response>>,
+);
+
+pub fn loopback() -> (Loopback, Server) {
+    let (requests, server_requests) = mpsc::unbounded();
+    let (server_responses, responses) = mpsc::unbounded();
+    (
+        Loopback {
+            requests,
+            responses,
+        },
+        (server_requests, server_responses),
+    )
+}
+
+/// Answer of the request `id`, sent by the test through the [`Server`] end of a [`Loopback`]
+pub fn respond(id: u32, response: Response) -> io::Result<protocol::Response> {
+    Ok(protocol::Response {
+        id: Some(id),
+        response: Some(response),
+        ..Default::default()
+    })
+}
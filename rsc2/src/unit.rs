@@ -0,0 +1,249 @@
+//! Views over the units of an observation.
+//!
+//! [`Unit`] borrows a `protocol::Unit` and gives non optional accessors, [`Units`] is a
+//! selection of units narrowed down with filters.
+use rsc2_pb::protocol::{self, Alliance};
+
+use crate::{
+    GameData, UnitTypeId,
+    geometry::{Point2, Point3},
+};
+
+/// A unit of an observation along with the game data describing its type
+#[derive(Debug, Clone, Copy)]
+pub struct Unit<'a> {
+    raw: &'a protocol::Unit,
+    data: &'a GameData,
+}
+
+impl<'a> Unit<'a> {
+    pub fn new(raw: &'a protocol::Unit, data: &'a GameData) -> Self {
+        Self { raw, data }
+    }
+    pub fn raw(&self) -> &'a protocol::Unit {
+        self.raw
+    }
+    pub fn data(&self) -> &'a GameData {
+        self.data
+    }
+    pub fn tag(&self) -> u64 {
+        self.raw.tag()
+    }
+    /// Raw unit type id, see [`type_id`](Self::type_id) for the typed id
+    pub fn unit_type(&self) -> u32 {
+        self.raw.unit_type()
+    }
+    /// `None` for unit types missing from `entities.json`
+    pub fn type_id(&self) -> Option<UnitTypeId> {
+        UnitTypeId::try_from(self.unit_type()).ok()
+    }
+    pub fn position(&self) -> Point2 {
+        self.position3d().into()
+    }
+    pub fn position3d(&self) -> Point3 {
+        self.raw.pos.map(Point3::from).unwrap_or_default()
+    }
+    pub fn distance_to(&self, point: impl Into<Point2>) -> f32 {
+        self.position().distance(point)
+    }
+    pub fn radius(&self) -> f32 {
+        self.raw.radius()
+    }
+    pub fn health(&self) -> f32 {
+        self.raw.health()
+    }
+    pub fn health_max(&self) -> f32 {
+        self.raw.health_max()
+    }
+    /// Health left between 0 and 1, 0 for units without health
+    pub fn health_fraction(&self) -> f32 {
+        match self.health_max() {
+            max if max > 0.0 => self.health() / max,
+            _ => 0.0,
+        }
+    }
+    pub fn shield(&self) -> f32 {
+        self.raw.shield()
+    }
+    pub fn energy(&self) -> f32 {
+        self.raw.energy()
+    }
+    pub fn orders(&self) -> &'a [protocol::UnitOrder] {
+        &self.raw.orders
+    }
+    pub fn is_idle(&self) -> bool {
+        self.raw.orders.is_empty()
+    }
+    /// Fully built
+    pub fn is_ready(&self) -> bool {
+        self.raw.build_progress() >= 1.0
+    }
+    pub fn is_mine(&self) -> bool {
+        self.raw.alliance() == Alliance::Self_
+    }
+    pub fn is_enemy(&self) -> bool {
+        self.raw.alliance() == Alliance::Enemy
+    }
+    pub fn is_flying(&self) -> bool {
+        self.raw.is_flying()
+    }
+    /// Whether the game data lists the unit type as a structure
+    pub fn is_structure(&self) -> bool {
+        self.data
+            .unit(self.unit_type())
+            .is_some_and(|data| data.is_structure)
+    }
+}
+
+/// A selection of units, filters return the matching units as a new selection
+#[derive(Debug, Clone, Default)]
+pub struct Units<'a> {
+    units: Vec<Unit<'a>>,
+}
+
+impl<'a> FromIterator<Unit<'a>> for Units<'a> {
+    fn from_iter<I: IntoIterator<Item = Unit<'a>>>(iter: I) -> Self {
+        Self {
+            units: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for Units<'a> {
+    type Item = Unit<'a>;
+    type IntoIter = std::vec::IntoIter<Unit<'a>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.units.into_iter()
+    }
+}
+
+impl<'a, 'u> IntoIterator for &'u Units<'a> {
+    type Item = &'u Unit<'a>;
+    type IntoIter = std::slice::Iter<'u, Unit<'a>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.units.iter()
+    }
+}
+
+impl<'a> Units<'a> {
+    pub fn new(raw: &'a [protocol::Unit], data: &'a GameData) -> Self {
+        raw.iter().map(|unit| Unit::new(unit, data)).collect()
+    }
+    /// Every unit of the raw observation, empty if the observation has no raw data
+    pub fn from_observation(
+        observation: &'a protocol::ResponseObservation,
+        data: &'a GameData,
+    ) -> Self {
+        let raw = observation
+            .observation
+            .as_ref()
+            .and_then(|observation| observation.raw_data.as_ref())
+            .map_or(&[][..], |raw| &raw.units[..]);
+        Self::new(raw, data)
+    }
+
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, Unit<'a>> {
+        self.units.iter()
+    }
+    pub fn first(&self) -> Option<Unit<'a>> {
+        self.units.first().copied()
+    }
+    pub fn tags(&self) -> Vec<u64> {
+        self.units.iter().map(Unit::tag).collect()
+    }
+
+    pub fn filter(&self, f: impl Fn(&Unit<'a>) -> bool) -> Self {
+        self.units.iter().filter(|unit| f(unit)).copied().collect()
+    }
+    pub fn of_type(&self, unit_type: impl Into<u32>) -> Self {
+        let unit_type = unit_type.into();
+        self.filter(|unit| unit.unit_type() == unit_type)
+    }
+    pub fn closer_than(&self, distance: f32, point: impl Into<Point2>) -> Self {
+        let point = point.into();
+        self.filter(|unit| unit.position().distance_squared(point) < distance * distance)
+    }
+    pub fn closest_to(&self, point: impl Into<Point2>) -> Option<Unit<'a>> {
+        let point = point.into();
+        self.units.iter().copied().min_by(|a, b| {
+            let (a, b) = (
+                a.position().distance_squared(point),
+                b.position().distance_squared(point),
+            );
+            a.total_cmp(&b)
+        })
+    }
+    pub fn idle(&self) -> Self {
+        self.filter(Unit::is_idle)
+    }
+    pub fn ready(&self) -> Self {
+        self.filter(Unit::is_ready)
+    }
+    pub fn mine(&self) -> Self {
+        self.filter(Unit::is_mine)
+    }
+    pub fn enemy(&self) -> Self {
+        self.filter(Unit::is_enemy)
+    }
+    pub fn structures(&self) -> Self {
+        self.filter(Unit::is_structure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(tag: u64, unit_type: UnitTypeId, (x, y): (f32, f32), idle: bool) -> protocol::Unit {
+        let mut unit = protocol::Unit {
+            tag: Some(tag),
+            unit_type: Some(unit_type.into()),
+            pos: Some(protocol::Point {
+                x: Some(x),
+                y: Some(y),
+                z: Some(0.0),
+            }),
+            build_progress: Some(1.0),
+            health: Some(30.0),
+            health_max: Some(45.0),
+            orders: if idle {
+                vec![]
+            } else {
+                vec![protocol::UnitOrder::default()]
+            },
+            ..Default::default()
+        };
+        unit.set_alliance(Alliance::Self_);
+        unit
+    }
+
+    #[test]
+    fn test_filters() {
+        let data = GameData::bundled();
+        let raw = [
+            unit(1, UnitTypeId::SCV, (0.0, 0.0), true),
+            unit(2, UnitTypeId::SCV, (5.0, 0.0), false),
+            unit(3, UnitTypeId::CommandCenter, (1.0, 1.0), true),
+        ];
+        let units = Units::new(&raw, &data);
+
+        let scvs = units.of_type(UnitTypeId::SCV);
+        assert_eq!(scvs.tags(), [1, 2]);
+        assert_eq!(scvs.idle().tags(), [1]);
+        assert_eq!(units.closer_than(2.0, (0.0, 0.0)).tags(), [1, 3]);
+        assert_eq!(units.closest_to((4.0, 0.0)).map(|unit| unit.tag()), Some(2));
+        assert_eq!(units.structures().tags(), [3]);
+        assert_eq!(units.mine().ready().len(), 3);
+        assert!(units.enemy().is_empty());
+        assert_eq!(
+            scvs.first().map(|scv| scv.health_fraction()),
+            Some(30.0 / 45.0)
+        );
+    }
+}